@@ -1,4 +1,4 @@
-use shared::game::{GameStateView, Visibility};
+use shared::game::{DoneView, GameStateView, ShapeChoice, Visibility};
 use web_sys::wasm_bindgen::JsCast;
 use web_sys::DomRect;
 use web_sys::{wasm_bindgen::JsValue, HtmlCanvasElement};
@@ -31,6 +31,7 @@ pub(crate) struct Board {
     pub(crate) input: Input,
     pub(crate) show_hidden: bool,
     pub(crate) edge_size: f64,
+    pub(crate) shape_choice: ShapeChoice,
 }
 
 impl Input {
@@ -309,22 +310,19 @@ impl Board {
         let is_scoring = matches!(game.state, GameStateView::Scoring(_));
         if !is_scoring {
             if let Some(selection_pos) = self.selection_pos {
-                let mut p = self.view_to_board_coord(game, selection_pos);
-                if game.mods.pixel {
-                    p.0 -= 1;
-                    p.1 -= 1;
-                }
+                let p = self.view_to_board_coord(game, selection_pos);
 
                 // TODO: This allocation is horrible, figure out how to avoid it
-                // TODO: Also move these to shared
-                let points = match game.mods.pixel {
-                    true => vec![
-                        (p.0, p.1),
-                        (p.0 + 1, p.1),
-                        (p.0, p.1 + 1),
-                        (p.0 + 1, p.1 + 1),
-                    ],
-                    false => vec![p],
+                let points = match game
+                    .mods
+                    .shape_rule()
+                    .and_then(|rule| rule.offsets(self.shape_choice))
+                {
+                    Some(offsets) => offsets
+                        .iter()
+                        .map(|&(dx, dy)| (p.0 + dx, p.1 + dy))
+                        .collect(),
+                    None => vec![p],
                 };
                 let color = turn;
                 // Teams start from 1
@@ -415,11 +413,40 @@ impl Board {
             }
         }
 
+        // King markers ///////////////////////////////////////////////////////
+
+        if history.is_none() {
+            for (idx, king) in game.kings.iter().enumerate() {
+                let (x, y) = match king {
+                    Some(p) => *p,
+                    None => continue,
+                };
+
+                // Don't mark an empty point if the king has been captured.
+                if board[y as usize * game.size.0 as usize + x as usize].is_empty() {
+                    continue;
+                }
+
+                context.set_stroke_style(&JsValue::from_str(dead_mark_color[idx]));
+                context.set_line_width(2.0);
+
+                self.board_to_view_coord(game, (x as i32, y as i32), |(px, py)| {
+                    context.stroke_rect(
+                        edge_size + (px as f64 + 0.3) * size,
+                        edge_size + (py as f64 + 0.3) * size,
+                        size * 0.4,
+                        size * 0.4,
+                    );
+                });
+            }
+        }
+
         // States /////////////////////////////////////////////////////////////
 
         if history.is_none() {
             match &game.state {
-                GameStateView::Scoring(scoring) | GameStateView::Done(scoring) => {
+                GameStateView::Scoring(scoring)
+                | GameStateView::Done(DoneView { scoring, .. }) => {
                     for group in &scoring.groups {
                         if group.alive {
                             continue;
@@ -15,7 +15,10 @@ use dioxus::{
 };
 use dioxus_router::prelude::*;
 use dioxus_signals::{use_selector, use_signal, ReadOnlySignal, Signal};
-use shared::{game::Seat, message::Profile};
+use shared::{
+    game::{Seat, ShapeChoice},
+    message::Profile,
+};
 use state::GameRoom;
 use web_sys::wasm_bindgen::JsCast;
 use window::DisplayMode;
@@ -111,10 +114,30 @@ fn GameRoute(cx: Scope, id: u32) -> Element {
     let state = state::use_state(cx);
     let mode = window::use_display_mode(cx);
 
+    let shape_choice = use_signal(cx, ShapeChoice::default);
+
     let _ = use_memo(cx, (id,), move |(id,)| {
         action.join_room(id);
     });
 
+    #[rustfmt::skip]
+    let error_class = sir::css!("
+        display: flex;
+        align-items: center;
+        justify-content: center;
+        height: 100%;
+        padding: 10px;
+    ");
+
+    let load_error = state.read().load_errors.read().get(id).cloned();
+    let panel = match load_error {
+        Some(error) => rsx!(div {
+            class: "{error_class}",
+            "This game can't be opened: {error}"
+        }),
+        None => rsx!(GamePanel { room: state.read().active_room(), shape_choice: shape_choice }),
+    };
+
     cx.render(rsx! {
         div {
             class: "root {mode.class()} in-game",
@@ -123,11 +146,11 @@ fn GameRoute(cx: Scope, id: u32) -> Element {
             }
             div {
                 class: "center-stack",
-                GameNavBar { room: state.read().active_room() },
+                GameNavBar { room: state.read().active_room(), shape_choice: shape_choice },
                 if mode.is_mobile() {
                     rsx!(SeatCards {})
                 }
-                GamePanel { room: state.read().active_room() }
+                panel
             }
             if mode.is_desktop() {
                 rsx!(RightPanel {})
@@ -205,7 +228,11 @@ fn RightPanel(cx: Scope) -> Element {
 }
 
 #[component]
-fn GamePanel(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Element {
+fn GamePanel(
+    cx: Scope,
+    room: ReadOnlySignal<Option<state::ActiveRoom>>,
+    shape_choice: Signal<ShapeChoice>,
+) -> Element {
     let outer_div = use_signal(cx, || None::<Rc<MountedData>>);
     let canvas_element = use_signal(cx, || None::<Rc<MountedData>>);
     let size = use_signal(cx, Size2D::default);
@@ -255,6 +282,13 @@ fn GamePanel(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Elem
         input: board::Input::None,
         show_hidden: false,
         edge_size: 40.0,
+        shape_choice: ShapeChoice::default(),
+    });
+
+    let shape_choice = *shape_choice;
+    dioxus_signals::use_effect(cx, move || {
+        let choice = *shape_choice.read();
+        board.write().shape_choice = choice;
     });
 
     dioxus_signals::use_effect(cx, move || {
@@ -287,7 +321,11 @@ fn GamePanel(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Elem
         board.selection_pos = input.into_selection();
 
         if let board::Input::Place(pos, true) = input {
-            action.place_stone(pos.0, pos.1);
+            if view.mods.shape.is_some() {
+                action.place_shape(pos.0, pos.1, *shape_choice.read());
+            } else {
+                action.place_stone(pos.0, pos.1);
+            }
         }
     };
 
@@ -365,10 +403,15 @@ fn CreateGameNavBar(cx: Scope) -> Element {
 }
 
 #[component]
-fn GameNavBar(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Element {
+fn GameNavBar(
+    cx: Scope,
+    room: ReadOnlySignal<Option<state::ActiveRoom>>,
+    shape_choice: Signal<ShapeChoice>,
+) -> Element {
     let mode = window::use_display_mode(cx);
     let state = state::use_state(cx);
     let room = *room;
+    let shape_choice = *shape_choice;
     let view =
         dioxus_signals::use_selector(cx, move || room.read().as_ref().map(|r| r.view.clone()));
 
@@ -411,14 +454,20 @@ fn GameNavBar(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Ele
     #[derive(Copy, Clone, Default, PartialEq)]
     struct Info {
         is_own_turn: bool,
+        is_seated: bool,
         is_play: bool,
         is_scoring: bool,
+        shape_count: usize,
+        can_rotate: bool,
     }
 
     let Info {
         is_own_turn,
+        is_seated,
         is_play,
         is_scoring,
+        shape_count,
+        can_rotate,
     } = *dioxus_signals::use_selector(cx, move || {
         let view = view.read();
         let Some(view) = view.as_ref() else {
@@ -428,12 +477,25 @@ fn GameNavBar(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Ele
         let seat = &view.seats[view.turn as usize];
         Info {
             is_own_turn: seat.player == Some(me),
+            is_seated: view.seats.iter().any(|s| s.player == Some(me)),
             is_play: matches!(view.state, shared::game::GameStateView::Play(_)),
             is_scoring: matches!(view.state, shared::game::GameStateView::Scoring(_)),
+            shape_count: view.mods.shape.as_ref().map_or(0, |s| s.shapes.len()),
+            can_rotate: view.mods.shape.as_ref().map_or(false, |s| s.allow_rotation),
         }
     })
     .read();
 
+    let next_shape = move || {
+        let mut choice = shape_choice.write();
+        choice.shape = (choice.shape + 1) % shape_count.max(1) as u32;
+    };
+    let shape_number = shape_choice.read().shape + 1;
+    let rotate = move || {
+        let mut choice = shape_choice.write();
+        choice.rotation = (choice.rotation + 1) % 4;
+    };
+
     let action = ActionSender::new(cx);
 
     cx.render(rsx! {
@@ -446,6 +508,18 @@ fn GameNavBar(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Ele
                 })
             }
             div { class: "pad" }
+            if is_play && shape_count > 1 {
+                rsx!(a {
+                    onclick: move |_| next_shape(),
+                    "Shape {shape_number}/{shape_count}"
+                })
+            }
+            if is_play && can_rotate {
+                rsx!(a {
+                    onclick: move |_| rotate(),
+                    "Rotate"
+                })
+            }
             if is_own_turn && is_play {
                 rsx!(a {
                     onclick: move |_| action.undo(),
@@ -458,6 +532,12 @@ fn GameNavBar(cx: Scope, room: ReadOnlySignal<Option<state::ActiveRoom>>) -> Ele
                     "Pass"
                 })
             }
+            if is_seated && is_play {
+                rsx!(a {
+                    onclick: move |_| action.propose_draw(),
+                    "Offer draw"
+                })
+            }
             if is_scoring {
                 rsx!(a {
                     onclick: move |_| action.pass(),
@@ -605,7 +685,7 @@ fn SeatCard(cx: Scope, seat: Seat, seat_id: u32) -> Element {
     };
 
     let done = match &view.state {
-        shared::game::GameStateView::Done(done) => Some(done.clone()),
+        shared::game::GameStateView::Done(done) => Some(done.scoring.clone()),
         _ => None,
     };
 
@@ -626,10 +706,22 @@ fn SeatCard(cx: Scope, seat: Seat, seat_id: u32) -> Element {
         let score = done.scores[seat.team.as_usize() - 1] as f32 / 2.0;
         rsx!(div {
             class: "scoring",
-            "Score: {score}"
+            "Score: {score}",
+            if done.draw {
+                " - (draw)"
+            } else if done.lost.contains(&seat.team) {
+                " - (lost)"
+            } else {
+                ""
+            }
         })
     } else if let Some(play) = &play {
         let passed = play.players_passed[seat.team.as_usize() - 1];
+        let draw_proposed = play
+            .draw_proposed
+            .get(seat_id as usize)
+            .copied()
+            .unwrap_or(false);
         rsx!(div {
             class: "scoring",
             if passed {
@@ -637,6 +729,11 @@ fn SeatCard(cx: Scope, seat: Seat, seat_id: u32) -> Element {
             } else {
                 ""
             }
+            if draw_proposed {
+                " - (offers a draw)"
+            } else {
+                ""
+            }
         })
     } else {
         rsx!(div {})
@@ -2,12 +2,12 @@ use gloo_storage::Storage;
 
 #[derive(Clone, PartialEq)]
 pub(crate) struct Palette {
-    pub shadow_stone_colors: [&'static str; 4],
-    pub shadow_border_colors: [&'static str; 4],
-    pub stone_colors: [&'static str; 4],
-    pub stone_colors_hidden: [&'static str; 4],
-    pub border_colors: [&'static str; 4],
-    pub dead_mark_color: [&'static str; 4],
+    pub shadow_stone_colors: [&'static str; 5],
+    pub shadow_border_colors: [&'static str; 5],
+    pub stone_colors: [&'static str; 5],
+    pub stone_colors_hidden: [&'static str; 5],
+    pub border_colors: [&'static str; 5],
+    pub dead_mark_color: [&'static str; 5],
     pub background: &'static str,
 }
 
@@ -40,21 +40,45 @@ impl PaletteOption {
     pub(crate) fn to_palette(&self) -> Palette {
         match self {
             PaletteOption::Normal => Palette {
-                shadow_stone_colors: ["#000000a0", "#eeeeeea0", "#5074bca0", "#e0658fa0"],
-                shadow_border_colors: ["#bbbbbb", "#555555", "#555555", "#555555"],
-                stone_colors: ["#000000", "#eeeeee", "#5074bc", "#e0658f"],
-                stone_colors_hidden: ["#00000080", "#eeeeee80", "#5074bc80", "#e0658f80"],
-                border_colors: ["#555555", "#000000", "#000000", "#000000"],
-                dead_mark_color: ["#eeeeee", "#000000", "#000000", "#000000"],
+                shadow_stone_colors: [
+                    "#000000a0",
+                    "#eeeeeea0",
+                    "#5074bca0",
+                    "#e0658fa0",
+                    "#888888a0",
+                ],
+                shadow_border_colors: ["#bbbbbb", "#555555", "#555555", "#555555", "#555555"],
+                stone_colors: ["#000000", "#eeeeee", "#5074bc", "#e0658f", "#888888"],
+                stone_colors_hidden: [
+                    "#00000080",
+                    "#eeeeee80",
+                    "#5074bc80",
+                    "#e0658f80",
+                    "#88888880",
+                ],
+                border_colors: ["#555555", "#000000", "#000000", "#000000", "#000000"],
+                dead_mark_color: ["#eeeeee", "#000000", "#000000", "#000000", "#000000"],
                 background: "#e0bb6c",
             },
             PaletteOption::Colorblind => Palette {
-                shadow_stone_colors: ["#000000a0", "#eeeeeea0", "#56b3e9a0", "#d52e00a0"],
-                shadow_border_colors: ["#bbbbbb", "#555555", "#555555", "#555555"],
-                stone_colors: ["#000000", "#eeeeee", "#56b3e9", "#d52e00"],
-                stone_colors_hidden: ["#00000080", "#eeeeee80", "#56b3e980", "#d52e0080"],
-                border_colors: ["#555555", "#000000", "#000000", "#000000"],
-                dead_mark_color: ["#eeeeee", "#000000", "#000000", "#000000"],
+                shadow_stone_colors: [
+                    "#000000a0",
+                    "#eeeeeea0",
+                    "#56b3e9a0",
+                    "#d52e00a0",
+                    "#888888a0",
+                ],
+                shadow_border_colors: ["#bbbbbb", "#555555", "#555555", "#555555", "#555555"],
+                stone_colors: ["#000000", "#eeeeee", "#56b3e9", "#d52e00", "#888888"],
+                stone_colors_hidden: [
+                    "#00000080",
+                    "#eeeeee80",
+                    "#56b3e980",
+                    "#d52e0080",
+                    "#88888880",
+                ],
+                border_colors: ["#555555", "#000000", "#000000", "#000000", "#000000"],
+                dead_mark_color: ["#eeeeee", "#000000", "#000000", "#000000", "#000000"],
                 background: "#e0bb6c",
             },
         }
@@ -17,6 +17,8 @@ pub(crate) struct ClientState {
     pub(crate) profiles: Signal<HashMap<u64, Profile>>,
     pub(crate) rooms: Signal<Vec<GameRoom>>,
     active_room: Signal<Option<ActiveRoom>>,
+    /// Why the server couldn't open a room, by room id
+    pub(crate) load_errors: Signal<HashMap<u32, String>>,
 }
 
 impl ClientState {
@@ -26,6 +28,7 @@ impl ClientState {
             profiles: Signal::new(HashMap::new()),
             rooms: Signal::new(Vec::new()),
             active_room: Signal::new(None),
+            load_errors: Signal::new(HashMap::new()),
         }
     }
 
@@ -61,6 +64,9 @@ pub(crate) struct GameView {
     pub(crate) points: Vec<i32>,
     pub(crate) move_number: u32,
     pub(crate) clock: Option<game::clock::GameClock>,
+    pub(crate) kings: Vec<Option<(u32, u32)>>,
+    pub(crate) seed_commitment: String,
+    pub(crate) resign_votes: Vec<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -166,6 +172,9 @@ pub(crate) fn use_state_provider(cx: &ScopeState) -> Signal<ClientState> {
                 points,
                 move_number,
                 clock,
+                kings,
+                seed_commitment,
+                resign_votes,
             } => {
                 let view = GameView {
                     state: game_state,
@@ -186,6 +195,9 @@ pub(crate) fn use_state_provider(cx: &ScopeState) -> Signal<ClientState> {
                     points,
                     move_number,
                     clock,
+                    kings,
+                    seed_commitment,
+                    resign_votes,
                 };
                 let room = ActiveRoom {
                     id: room_id,
@@ -196,6 +208,10 @@ pub(crate) fn use_state_provider(cx: &ScopeState) -> Signal<ClientState> {
                 *state.active_room.write() = Some(room);
                 log::debug!("{:?}", &*state.active_room.read());
             }
+            ServerMessage::Error(message::Error::GameLoad { room_id, error }) => {
+                log::error!("Game {} can't be opened: {}", room_id, error);
+                state.load_errors.write().insert(room_id, error.to_string());
+            }
             _ => {}
         }
     };
@@ -289,7 +305,7 @@ impl<'a> ActionSender<'a> {
     }
 
     pub(crate) fn start_game(&self, start: message::StartGame) {
-        let msg = ClientMessage::StartGame(start);
+        let msg = ClientMessage::StartGame(Box::new(start));
         self.send(msg);
     }
 
@@ -300,6 +316,13 @@ impl<'a> ActionSender<'a> {
         })
     }
 
+    pub(crate) fn place_shape(&self, x: u32, y: u32, choice: game::ShapeChoice) {
+        self.send(ClientMessage::GameAction {
+            room_id: None,
+            action: shared::message::GameAction::PlaceShape(x, y, choice),
+        })
+    }
+
     pub(crate) fn undo(&self) {
         self.send(ClientMessage::GameAction {
             room_id: None,
@@ -314,6 +337,13 @@ impl<'a> ActionSender<'a> {
         })
     }
 
+    pub(crate) fn propose_draw(&self) {
+        self.send(ClientMessage::GameAction {
+            room_id: None,
+            action: shared::message::GameAction::ProposeDraw,
+        })
+    }
+
     pub(crate) fn resign(&self) {
         self.send(ClientMessage::GameAction {
             room_id: None,
@@ -5,7 +5,10 @@ use crate::{
     state::{self, ActionSender},
     window,
 };
-use shared::{game::GameModifier, message};
+use shared::{
+    game::{setup, GameModifier},
+    message,
+};
 
 macro_rules! simple_modifier {
     ($name:ident, $modifiers:ident => $select:expr, $flip:expr, $text:expr, $tooltip:expr) => {
@@ -74,7 +77,8 @@ pub fn CreateGamePanel(cx: Scope) -> Element {
 
     let start = dioxus_signals::use_selector(cx, move || {
         let preset = chosen_preset.read().clone();
-        let seats = match preset {
+        let mods = modifiers.read().clone();
+        let mut seats = match preset {
             Preset::Standard => vec![1, 2],
             Preset::Rengo => vec![1, 2, 1, 2],
             Preset::ThreeColor => vec![1, 2, 3],
@@ -90,12 +94,17 @@ pub fn CreateGamePanel(cx: Scope) -> Element {
             Preset::ThreeColorRengo => vec![0, 0, 0],
         };
 
+        // Zen go has one more player than colors, so nobody keeps the same color
+        if mods.zen_go.is_some() {
+            seats.push(1);
+        }
+
         message::StartGame {
             name: game_name.read().clone(),
             seats,
             komis,
-            size: (19, 19),
-            mods: modifiers.read().clone(),
+            size: mods.start_position.as_ref().map_or((19, 19), |p| p.size),
+            mods,
         }
     });
 
@@ -144,12 +153,25 @@ fn CreateGameButton(cx: Scope, start: ReadOnlySignal<message::StartGame>) -> Ele
 
     let action = ActionSender::new(cx);
 
+    let errors = {
+        let start = start.read();
+        setup::validate(&start.seats, start.komis.len(), start.size, &start.mods)
+            .err()
+            .unwrap_or_default()
+    };
+
     cx.render(rsx! {
         div {
             button {
+                disabled: !errors.is_empty(),
                 onclick: move |_| action.start_game(start.read().clone()),
                 "Start Game"
             }
+            ul {
+                for error in &errors {
+                    li { "{error}" }
+                }
+            }
         }
     })
 }
@@ -194,6 +216,15 @@ fn ModifierSelectors(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
             PonnukiIsPoints { modifiers: modifiers }
             Observable { modifiers: modifiers }
             NoUndo { modifiers: modifiers }
+            NeutralStones { modifiers: modifiers }
+            RandomStart { modifiers: modifiers }
+            LastSurvivor { modifiers: modifiers }
+            Alliances { modifiers: modifiers }
+            KingGo { modifiers: modifiers }
+            ShapeGo { modifiers: modifiers }
+            MoveLimit { modifiers: modifiers }
+            TeamResign { modifiers: modifiers }
+            StartPosition { modifiers: modifiers }
         }
     })
 }
@@ -201,16 +232,61 @@ fn ModifierSelectors(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
 // TODO: Traitor go Traitor stones:
 // TODO: Ponnuki is: points (can be negative)
 
-simple_modifier!(
-    OneColorGo,
-    modifiers => modifiers.visibility_mode.is_some(),
-    modifiers.visibility_mode = match modifiers.visibility_mode {
-        Some(_) => None,
-        None => Some(shared::game::VisibilityMode::OneColor),
-    },
-    "One color go",
-    "Everyone sees the stones as same color. Confusion ensues."
-);
+#[component]
+fn OneColorGo(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    use shared::game::{Color, OneColorChoice, VisibilityMode};
+
+    let modifiers = *modifiers;
+    let choice = use_signal(cx, || OneColorChoice::Fixed(Color(1)));
+
+    dioxus_signals::use_effect(cx, move || {
+        let choice = *choice.read();
+        if let Some(mode) = &mut modifiers.write().visibility_mode {
+            *mode = VisibilityMode::OneColor(choice);
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.visibility_mode = match modifiers.visibility_mode {
+            Some(_) => None,
+            None => Some(VisibilityMode::OneColor(*choice.read())),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().visibility_mode.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "One color go"
+                span {
+                    class: "tooltip-text",
+                    "Everyone sees the stones as same color. Confusion ensues."
+                }
+            }
+            span {
+                class: "adjust",
+                ", color: "
+                select {
+                    onchange: move |e| choice.set(match e.inner().value.as_str() {
+                        "white" => OneColorChoice::Fixed(Color(2)),
+                        "random" => OneColorChoice::Random,
+                        _ => OneColorChoice::Fixed(Color(1)),
+                    }),
+                    option { value: "black", "Black" }
+                    option { value: "white", "White" }
+                    option { value: "random", "Random" }
+                }
+            }
+        }
+    })
+}
 
 simple_modifier!(
     PixelGo,
@@ -220,7 +296,6 @@ simple_modifier!(
     "You place 2x2 blobs. Overlapping stones are ignored."
 );
 
-// TODO: Ensure zen go receives the correct color count from the preset
 simple_modifier!(
     ZenGo,
     modifiers => modifiers.zen_go.is_some(),
@@ -300,6 +375,17 @@ simple_modifier!(
     "Disables undo for all players."
 );
 
+simple_modifier!(
+    KingGo,
+    modifiers => modifiers.king.is_some(),
+    modifiers.king = match modifiers.king {
+        Some(_) => None,
+        None => Some(shared::game::KingGo {}),
+    },
+    "King go",
+    "Your first stone is your king. If it gets captured, you lose. Opponents can't see your king if stones are hidden."
+);
+
 #[component]
 fn HiddenMoveGo(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
     let modifiers = *modifiers;
@@ -507,6 +593,487 @@ fn PonnukiIsPoints(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
     })
 }
 
+#[component]
+fn NeutralStones(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    let modifiers = *modifiers;
+    let stone_count = use_signal(cx, || 8);
+    let capturable = use_signal(cx, || false);
+
+    dioxus_signals::use_effect(cx, move || {
+        let count = *stone_count.read();
+        let capturable = *capturable.read();
+        if let Some(mode) = &mut modifiers.write().neutral_stones {
+            mode.count = count;
+            mode.capturable = capturable;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.neutral_stones = match modifiers.neutral_stones {
+            Some(_) => None,
+            None => Some(shared::game::NeutralStones {
+                count: *stone_count.read(),
+                capturable: *capturable.read(),
+            }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().neutral_stones.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Neutral stones"
+                span {
+                    class: "tooltip-text",
+                    "Gray stones are scattered on the board before the game starts. They belong to no one and never count as territory."
+                }
+            }
+            span {
+                class: "adjust",
+                ", count: "
+                input {
+                    r#type: "number",
+                    value: "{stone_count}",
+                    onchange: move |e| stone_count.set(e.inner().value.parse().unwrap())
+                }
+            }
+            span {
+                class: "adjust",
+                input {
+                    r#type: "checkbox",
+                    checked: *capturable.read(),
+                    onclick: move |_| {
+                        let value = !*capturable.read();
+                        capturable.set(value);
+                    },
+                }
+                " capturable"
+            }
+        }
+    })
+}
+
+#[component]
+fn RandomStart(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    let modifiers = *modifiers;
+    let stone_count = use_signal(cx, || 2);
+
+    dioxus_signals::use_effect(cx, move || {
+        let count = *stone_count.read();
+        if let Some(mode) = &mut modifiers.write().random_start {
+            mode.stones_per_team = count;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.random_start = match modifiers.random_start {
+            Some(_) => None,
+            None => Some(shared::game::RandomStart {
+                stones_per_team: *stone_count.read(),
+            }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().random_start.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Random start"
+                span {
+                    class: "tooltip-text",
+                    "Every team starts with stones on random points."
+                }
+            }
+            span {
+                class: "adjust",
+                ", stones per team: "
+                input {
+                    r#type: "number",
+                    value: "{stone_count}",
+                    onchange: move |e| stone_count.set(e.inner().value.parse().unwrap())
+                }
+            }
+        }
+    })
+}
+
+#[component]
+fn LastSurvivor(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    let modifiers = *modifiers;
+    let grace_moves = use_signal(cx, || 12);
+    let min_stones = use_signal(cx, || 1);
+
+    dioxus_signals::use_effect(cx, move || {
+        let grace_moves = *grace_moves.read();
+        let min_stones = *min_stones.read();
+        if let Some(mode) = &mut modifiers.write().last_survivor {
+            mode.grace_moves = grace_moves;
+            mode.min_stones = min_stones;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.last_survivor = match modifiers.last_survivor {
+            Some(_) => None,
+            None => Some(shared::game::LastSurvivor {
+                grace_moves: *grace_moves.read(),
+                min_stones: *min_stones.read(),
+            }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().last_survivor.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Last survivor"
+                span {
+                    class: "tooltip-text",
+                    "A team with too few stones on the board is out of the game. The last team standing wins. Needs at least three teams."
+                }
+            }
+            span {
+                class: "adjust",
+                ", grace moves: "
+                input {
+                    r#type: "number",
+                    value: "{grace_moves}",
+                    onchange: move |e| grace_moves.set(e.inner().value.parse().unwrap())
+                }
+            }
+            span {
+                class: "adjust",
+                ", min stones: "
+                input {
+                    r#type: "number",
+                    value: "{min_stones}",
+                    onchange: move |e| min_stones.set(e.inner().value.parse().unwrap())
+                }
+            }
+        }
+    })
+}
+
+#[component]
+fn Alliances(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    let modifiers = *modifiers;
+    let teams = use_signal(cx, || "1 1 2".to_string());
+
+    let parse = move || {
+        teams
+            .read()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(|x| x.parse().ok())
+            .collect::<Vec<u8>>()
+    };
+
+    dioxus_signals::use_effect(cx, move || {
+        let teams = parse();
+        if let Some(mode) = &mut modifiers.write().alliances {
+            mode.teams = teams;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.alliances = match modifiers.alliances {
+            Some(_) => None,
+            None => Some(shared::game::Alliances { teams: parse() }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().alliances.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Alliances"
+                span {
+                    class: "tooltip-text",
+                    "Colors in the same alliance share liberties and can't capture each other. Scores are still separate. Give an alliance number for each color, eg. \"1 1 2\"."
+                }
+            }
+            span {
+                class: "adjust",
+                ", alliances: "
+                input {
+                    r#type: "text",
+                    value: "{teams}",
+                    oninput: move |e| teams.set(e.value.clone()),
+                }
+            }
+        }
+    })
+}
+
+#[component]
+fn MoveLimit(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    let modifiers = *modifiers;
+    let moves = use_signal(cx, || 300);
+
+    dioxus_signals::use_effect(cx, move || {
+        let moves = *moves.read();
+        if let Some(mode) = &mut modifiers.write().move_limit {
+            mode.moves = moves;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.move_limit = match modifiers.move_limit {
+            Some(_) => None,
+            None => Some(shared::game::MoveLimit {
+                moves: *moves.read(),
+            }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().move_limit.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Move limit"
+                span {
+                    class: "tooltip-text",
+                    "The game goes to scoring after this many moves. Passes count as moves."
+                }
+            }
+            span {
+                class: "adjust",
+                ", moves: "
+                input {
+                    r#type: "number",
+                    value: "{moves}",
+                    onchange: move |e| moves.set(e.inner().value.parse().unwrap())
+                }
+            }
+        }
+    })
+}
+
+#[component]
+fn TeamResign(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    use shared::game::TeamResign;
+
+    let modifiers = *modifiers;
+    let rule = use_signal(cx, || TeamResign::Majority);
+
+    dioxus_signals::use_effect(cx, move || {
+        let rule = *rule.read();
+        if let Some(mode) = &mut modifiers.write().team_resign {
+            *mode = rule;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.team_resign = match modifiers.team_resign {
+            Some(_) => None,
+            None => Some(*rule.read()),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().team_resign.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Team resign"
+                span {
+                    class: "tooltip-text",
+                    "In rengo, the team resigns together once enough of its players have resigned."
+                }
+            }
+            span {
+                class: "adjust",
+                ", needs: "
+                select {
+                    onchange: move |e| rule.set(match e.inner().value.as_str() {
+                        "any" => TeamResign::Any,
+                        "all" => TeamResign::All,
+                        _ => TeamResign::Majority,
+                    }),
+                    option { value: "majority", "Majority" }
+                    option { value: "any", "Any player" }
+                    option { value: "all", "All players" }
+                }
+            }
+        }
+    })
+}
+
+#[component]
+fn StartPosition(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    use shared::game::sgf::{self, Load};
+
+    let modifiers = *modifiers;
+    let text = use_signal(cx, String::new);
+    let load = use_signal(cx, || Load::FinalPosition);
+
+    let position = dioxus_signals::use_selector(cx, move || {
+        let text = text.read();
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(sgf::load_position(&text, *load.read()))
+    });
+
+    dioxus_signals::use_effect(cx, move || {
+        let position = position.read().clone().and_then(Result::ok);
+        let mut modifiers = modifiers.write();
+        if modifiers.start_position != position {
+            modifiers.start_position = position;
+        }
+    });
+
+    let status = match &*position.read() {
+        None => String::new(),
+        Some(Ok(position)) => format!(
+            "{}x{} board, {} to move",
+            position.size.0,
+            position.size.1,
+            shared::game::Color::name(position.to_move)
+        ),
+        Some(Err(error)) => error.to_string(),
+    };
+
+    cx.render(rsx! {
+        li {
+            label {
+                class: "tooltip",
+                "Start from SGF"
+                span {
+                    class: "tooltip-text",
+                    "Paste an SGF file to start from its position. The board size comes from the file."
+                }
+            }
+            span {
+                class: "adjust",
+                select {
+                    onchange: move |e| load.set(match e.inner().value.as_str() {
+                        "setup" => Load::Setup,
+                        _ => Load::FinalPosition,
+                    }),
+                    option { value: "final", "Final position" }
+                    option { value: "setup", "Setup stones only" }
+                }
+            }
+            div {
+                textarea {
+                    rows: 4,
+                    value: "{text}",
+                    oninput: move |e| text.set(e.value.clone()),
+                }
+            }
+            div { "{status}" }
+        }
+    })
+}
+
+#[component]
+fn ShapeGo(cx: Scope, modifiers: Signal<GameModifier>) -> Element {
+    use shared::game::Shape;
+
+    let modifiers = *modifiers;
+    let allow_rotation = use_signal(cx, || true);
+
+    dioxus_signals::use_effect(cx, move || {
+        let allow_rotation = *allow_rotation.read();
+        if let Some(mode) = &mut modifiers.write().shape {
+            mode.allow_rotation = allow_rotation;
+        }
+    });
+
+    let flip = move || {
+        let mut modifiers = modifiers.write();
+        modifiers.shape = match modifiers.shape {
+            Some(_) => None,
+            None => Some(shared::game::ShapeGo {
+                shapes: vec![
+                    Shape {
+                        points: vec![(0, 0), (1, 0)],
+                    },
+                    Shape {
+                        points: vec![(0, 0), (1, 0), (0, 1)],
+                    },
+                    Shape {
+                        points: vec![(-1, 0), (0, 0), (1, 0), (0, 1)],
+                    },
+                ],
+                allow_rotation: *allow_rotation.read(),
+            }),
+        };
+    };
+
+    cx.render(rsx! {
+        li {
+            input {
+                r#type: "checkbox",
+                checked: modifiers.read().shape.is_some(),
+                onclick: move |_| flip(),
+            }
+            label {
+                class: "tooltip",
+                onclick: move |_| flip(),
+                "Shape go"
+                span {
+                    class: "tooltip-text",
+                    "You place a domino, an L or a T shape on every move. Overlapping stones are ignored. Can't be combined with pixel go."
+                }
+            }
+            span {
+                class: "adjust",
+                input {
+                    r#type: "checkbox",
+                    checked: *allow_rotation.read(),
+                    onclick: move |_| {
+                        let value = !*allow_rotation.read();
+                        allow_rotation.set(value);
+                    },
+                }
+                " rotation"
+            }
+        }
+    })
+}
+
 #[component]
 fn PresetSelectors(cx: Scope, chosen_preset: Signal<Preset>) -> Element {
     let presets = [
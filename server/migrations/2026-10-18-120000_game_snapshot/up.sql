@@ -0,0 +1,2 @@
+ALTER TABLE games
+ADD COLUMN snapshot BYTEA;
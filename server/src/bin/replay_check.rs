@@ -0,0 +1,259 @@
+//! Loads every stored game and reports the ones that no longer play back correctly.
+//! Run it before deploying rule changes.
+//!
+//! Usage:
+//!
+//!     replay_check [--db URL] [--copy FILE] [--redump DIR] [REPLAY...]
+//!
+//! Without arguments the games are read from `DATABASE_URL`. `--copy` reads the output of
+//! `\copy (SELECT id, replay, snapshot FROM games) TO 'FILE'` instead, and any other arguments
+//! are read as single replay files. `--redump DIR` writes every game that loads back out in
+//! the current replay format, named by the game id.
+//!
+//! Games with an up to date snapshot are also loaded from it, and have to match the replay.
+//!
+//! Exits with an error if any game fails.
+
+#[macro_use]
+extern crate diesel;
+
+#[path = "../schema.rs"]
+mod schema;
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use shared::game::Game;
+
+/// Game id or file name, and the replay and snapshot if there are ones.
+type Row = (String, Option<Vec<u8>>, Option<Vec<u8>>);
+
+struct Options {
+    database_url: Option<String>,
+    copy_files: Vec<PathBuf>,
+    replay_files: Vec<PathBuf>,
+    redump: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        database_url: None,
+        copy_files: Vec::new(),
+        replay_files: Vec::new(),
+        redump: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--db" => options.database_url = Some(value()?),
+            "--copy" => options.copy_files.push(value()?.into()),
+            "--redump" => options.redump = Some(value()?.into()),
+            "--help" | "-h" => return Err(String::new()),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => options.replay_files.push(arg.into()),
+        }
+    }
+
+    if options.copy_files.is_empty() && options.replay_files.is_empty() {
+        dotenv::dotenv().ok();
+        if options.database_url.is_none() {
+            options.database_url = env::var("DATABASE_URL").ok();
+        }
+        if options.database_url.is_none() {
+            return Err("No games to check: set DATABASE_URL or give files".to_string());
+        }
+    }
+
+    Ok(options)
+}
+
+/// Reads the id, replay and snapshot of every game.
+fn read_database(url: &str) -> Result<Vec<Row>, String> {
+    use schema::games::dsl::*;
+
+    let connection = PgConnection::establish(url).map_err(|e| e.to_string())?;
+    let rows = games
+        .select((id, replay, snapshot))
+        .order(id)
+        .load::<(i64, Option<Vec<u8>>, Option<Vec<u8>>)>(&connection)
+        .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(i, r, s)| (i.to_string(), r, s))
+        .collect())
+}
+
+/// Reads the text format of `COPY`: one game per line, columns separated by tabs and
+/// bytea written as hex.
+fn read_copy(path: &PathBuf) -> Result<Vec<Row>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let mut rows = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |msg: &str| format!("{}:{}: {}", path.display(), line_number + 1, msg);
+
+        let mut columns = line.split('\t');
+        let game_id = columns.next().ok_or_else(|| error("missing id"))?;
+        let data = columns.next().ok_or_else(|| error("missing replay"))?;
+
+        let replay = decode_bytea(data).map_err(error)?;
+        // Older dumps have no snapshots
+        let snapshot = match columns.next() {
+            Some(data) => decode_bytea(data).map_err(error)?,
+            None => None,
+        };
+        rows.push((game_id.to_string(), replay, snapshot));
+    }
+
+    Ok(rows)
+}
+
+/// `None` for NULL.
+fn decode_bytea(data: &str) -> Result<Option<Vec<u8>>, &'static str> {
+    if data == "\\N" {
+        return Ok(None);
+    }
+    let hex = data
+        .strip_prefix("\\\\x")
+        .or_else(|| data.strip_prefix("\\x"))
+        .ok_or("bytea is not hex encoded")?;
+    decode_hex(hex).map(Some).ok_or("invalid hex")
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => Some(hex_digit(*a)? << 4 | hex_digit(*b)?),
+            _ => None,
+        })
+        .collect()
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|d| d as u8)
+}
+
+fn differs(a: &Game, b: &Game) -> bool {
+    a.shared.board != b.shared.board
+        || a.shared.points != b.shared.points
+        || a.shared.turn != b.shared.turn
+        || a.shared.board_history.len() != b.shared.board_history.len()
+        || a.state != b.state
+}
+
+/// Loads the game and checks that it survives a dump and a reload unchanged, and that the
+/// snapshot, if it's up to date, gives the same game.
+fn check(replay: &[u8], snapshot: Option<&[u8]>) -> Result<Game, String> {
+    let game = Game::load(replay).map_err(|e| e.to_string())?;
+
+    let errors = game.consistency_errors();
+    if !errors.is_empty() {
+        return Err(errors.join(", "));
+    }
+
+    let reloaded = Game::load(&game.dump()).map_err(|e| format!("reload failed: {}", e))?;
+    if differs(&reloaded, &game) {
+        return Err("reloaded game differs".to_string());
+    }
+
+    if let Some(snapshot) = snapshot {
+        match Game::load_snapshot(replay, snapshot) {
+            Ok(Some(loaded)) if differs(&loaded, &game) => {
+                return Err("snapshot differs from the replay".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("snapshot failed to load: {}", e)),
+        }
+    }
+
+    Ok(game)
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(error) => {
+            if !error.is_empty() {
+                eprintln!("{}", error);
+            }
+            eprintln!("Usage: replay_check [--db URL] [--copy FILE] [--redump DIR] [REPLAY...]");
+            process::exit(2);
+        }
+    };
+
+    let mut rows = Vec::new();
+    let mut read = |result: Result<Vec<_>, String>| match result {
+        Ok(mut r) => rows.append(&mut r),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    };
+
+    if let Some(url) = &options.database_url {
+        read(read_database(url));
+    }
+    for path in &options.copy_files {
+        read(read_copy(path));
+    }
+    for path in &options.replay_files {
+        read(
+            fs::read(path)
+                .map(|replay| vec![(path.display().to_string(), Some(replay), None)])
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+        );
+    }
+
+    if let Some(dir) = &options.redump {
+        if let Err(error) = fs::create_dir_all(dir) {
+            eprintln!("{}: {}", dir.display(), error);
+            process::exit(2);
+        }
+    }
+
+    let mut failed = 0;
+    let mut empty = 0;
+    for (game_id, replay, snapshot) in &rows {
+        let replay = match replay {
+            Some(replay) => replay,
+            None => {
+                empty += 1;
+                continue;
+            }
+        };
+
+        match check(replay, snapshot.as_deref()) {
+            Ok(game) => {
+                if let Some(dir) = &options.redump {
+                    let name = game_id.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+                    let path = dir.join(format!("{}.replay", name));
+                    if let Err(error) = fs::write(&path, game.dump()) {
+                        eprintln!("{}: {}", path.display(), error);
+                        failed += 1;
+                    }
+                }
+            }
+            Err(error) => {
+                println!("{}: {}", game_id, error);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} games checked, {} failed, {} without a replay",
+        rows.len() - empty,
+        failed,
+        empty
+    );
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
@@ -46,6 +46,7 @@ pub struct Game {
     pub name: String,
     pub replay: Option<Vec<u8>>,
     pub owner: Option<i64>,
+    pub snapshot: Option<Vec<u8>>,
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -55,6 +56,7 @@ pub struct NewGame<'a> {
     pub name: &'a str,
     pub replay: Option<&'a [u8]>,
     pub owner: Option<i64>,
+    pub snapshot: Option<&'a [u8]>,
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -89,6 +91,8 @@ pub struct StoreGame {
     pub owner: Option<u64>,
     pub name: String,
     pub replay: Option<Vec<u8>>,
+    /// Left as it is if `None`
+    pub snapshot: Option<Vec<u8>>,
 }
 
 impl Message for StoreGame {
@@ -208,6 +212,7 @@ impl Handler<StoreGame> for DbActor {
             owner: msg.owner.map(|x| x as _),
             name: &msg.name,
             replay: msg.replay.as_deref(),
+            snapshot: msg.snapshot.as_deref(),
         };
 
         let result = match msg.id {
@@ -83,6 +83,13 @@ impl actix::Message for GetAdminView {
     type Result = Result<game::GameView, message::Error>;
 }
 
+/// The native game record, only for finished games so nothing hidden leaks.
+pub struct GetRecord;
+
+impl actix::Message for GetRecord {
+    type Result = Result<String, message::Error>;
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 //                                   Actor                                   //
 ///////////////////////////////////////////////////////////////////////////////
@@ -101,9 +108,32 @@ pub struct GameRoom {
     /// Kicked players are not visible to other users in the game and can not
     /// hold seats. They can still follow the game.
     pub kicked_players: HashSet<u64>,
+
+    /// Nicks of the seat holders, for game records
+    pub nicks: HashMap<u64, String>,
 }
 
 impl GameRoom {
+    /// Looks up the nicks of the seat holders for game records.
+    // .. this is not great
+    fn fetch_nicks(&self, ctx: &mut Context<Self>) {
+        for seat in &self.game.shared.seats {
+            if let Some(user_id) = seat.player {
+                let fut = self.server.send(server::QueryProfile { user_id });
+                ctx.spawn(fut.into_actor(self).map(|res, act, _| {
+                    if let Ok(Ok(server::Profile {
+                        user_id,
+                        nick: Some(nick),
+                        ..
+                    })) = res
+                    {
+                        act.nicks.insert(user_id, nick);
+                    }
+                }));
+            }
+        }
+    }
+
     fn send_room_messages(&self, mut create_msg: impl FnMut(u64) -> Message) {
         for (user_id, addr) in self.sessions.values() {
             let _ = addr.do_send(create_msg(*user_id));
@@ -145,6 +175,14 @@ impl GameRoom {
                 .game
                 .make_action(user_id, game::ActionKind::Place(x, y), current_time)
                 .map_err(Into::into),
+            message::GameAction::PlaceShape(x, y, choice) => self
+                .game
+                .make_action(
+                    user_id,
+                    game::ActionKind::PlaceShape(x, y, choice),
+                    current_time,
+                )
+                .map_err(Into::into),
             message::GameAction::Pass => self
                 .game
                 .make_action(user_id, game::ActionKind::Pass, current_time)
@@ -157,6 +195,10 @@ impl GameRoom {
                 .game
                 .make_action(user_id, game::ActionKind::Resign, current_time)
                 .map_err(Into::into),
+            message::GameAction::ProposeDraw => self
+                .game
+                .make_action(user_id, game::ActionKind::ProposeDraw, current_time)
+                .map_err(Into::into),
             message::GameAction::TakeSeat(seat_id) => {
                 if self.kicked_players.contains(&user_id) {
                     return Err(Error::other("Kicked from game"));
@@ -195,7 +237,7 @@ impl GameRoom {
                 if !game_done {
                     return Err(Error::other("Game not finished"));
                 }
-                let sgf = game::export::sgf_export(&self.game);
+                let sgf = game::export::sgf_export(&self.game, &self.nicks);
                 let _ = addr.do_send(Message::SGF {
                     room_id: self.room_id,
                     sgf,
@@ -230,6 +272,12 @@ impl GameRoom {
             id: Some(self.room_id as _),
             name: self.name.clone(),
             replay: Some(self.game.dump()),
+            // The actions since the last snapshot are replayed when loading
+            snapshot: if self.game.actions.len() % game::snapshot::SNAPSHOT_INTERVAL == 0 {
+                Some(self.game.snapshot())
+            } else {
+                None
+            },
             owner: self.owner,
         });
 
@@ -245,6 +293,15 @@ impl Actor for GameRoom {
     fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
         println!("Room {} stopping!", self.room_id);
 
+        // Next time the room loads it doesn't have to replay the game
+        self.db.do_send(db::StoreGame {
+            id: Some(self.room_id as _),
+            name: self.name.clone(),
+            replay: Some(self.game.dump()),
+            snapshot: Some(self.game.snapshot()),
+            owner: self.owner,
+        });
+
         Running::Stop
     }
 }
@@ -268,7 +325,7 @@ impl Handler<Leave> for GameRoom {
 impl Handler<Join> for GameRoom {
     type Result = ();
 
-    fn handle(&mut self, msg: Join, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: Join, ctx: &mut Self::Context) -> Self::Result {
         let Join {
             session_id,
             user_id,
@@ -281,20 +338,14 @@ impl Handler<Join> for GameRoom {
 
         // TODO: Announce profile to room members
 
-        // Broadcast the profile of each seatholder
-        // .. this is not great
-        for seat in &self.game.shared.seats {
-            if let Some(user_id) = seat.player {
-                self.server.do_send(server::QueryProfile { user_id });
-            }
-        }
+        self.fetch_nicks(ctx);
     }
 }
 
 impl Handler<GameAction> for GameRoom {
     type Result = MessageResult<GameAction>;
 
-    fn handle(&mut self, msg: GameAction, _: &mut Context<Self>) -> MessageResult<GameAction> {
+    fn handle(&mut self, msg: GameAction, ctx: &mut Context<Self>) -> MessageResult<GameAction> {
         use message::Error;
 
         let GameAction { id, action } = msg;
@@ -307,7 +358,13 @@ impl Handler<GameAction> for GameRoom {
         };
         let addr = addr.clone();
 
-        MessageResult(self.make_action(user_id, action, Some(addr)))
+        let took_seat = matches!(action, message::GameAction::TakeSeat(_));
+        let res = self.make_action(user_id, action, Some(addr));
+        if took_seat && res.is_ok() {
+            self.fetch_nicks(ctx);
+        }
+
+        MessageResult(res)
     }
 }
 
@@ -317,11 +374,17 @@ impl Handler<GameActionAsUser> for GameRoom {
     fn handle(
         &mut self,
         msg: GameActionAsUser,
-        _: &mut Context<Self>,
+        ctx: &mut Context<Self>,
     ) -> MessageResult<GameActionAsUser> {
         let GameActionAsUser { user_id, action } = msg;
 
-        MessageResult(self.make_action(user_id, action, None))
+        let took_seat = matches!(action, message::GameAction::TakeSeat(_));
+        let res = self.make_action(user_id, action, None);
+        if took_seat && res.is_ok() {
+            self.fetch_nicks(ctx);
+        }
+
+        MessageResult(res)
     }
 }
 
@@ -340,3 +403,14 @@ impl Handler<GetAdminView> for GameRoom {
         Ok(self.game.get_view(0))
     }
 }
+
+impl Handler<GetRecord> for GameRoom {
+    type Result = <GetRecord as actix::Message>::Result;
+
+    fn handle(&mut self, _: GetRecord, _ctx: &mut Self::Context) -> Self::Result {
+        match self.game.state {
+            game::GameState::Done(_) => Ok(self.game.export_record()),
+            _ => Err(message::Error::other("Game is not over")),
+        }
+    }
+}
@@ -142,6 +142,9 @@ impl Handler<game_room::Message> for ClientWebSocket {
                         points: view.points.to_vec(),
                         move_number: view.move_number,
                         clock: view.clock,
+                        kings: view.kings.to_vec(),
+                        seed_commitment: view.seed_commitment,
+                        resign_votes: view.resign_votes.to_vec(),
                     }
                     .pack(),
                 );
@@ -332,10 +335,16 @@ impl ClientWebSocket {
                 },
             })
             .into_actor(self)
-            .then(move |res, act, _| {
-                if let Ok(Ok(addr)) = res {
-                    act.room_id = Some(room_id);
-                    act.game_addr.insert(room_id, addr);
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Ok(addr)) => {
+                        act.room_id = Some(room_id);
+                        act.game_addr.insert(room_id, addr);
+                    }
+                    Ok(Err(err)) => {
+                        ctx.binary(ServerMessage::Error(err).pack());
+                    }
+                    _ => {}
                 }
                 fut::ready(())
             })
@@ -391,7 +400,7 @@ impl ClientWebSocket {
                 self.handle_get_game_list(ctx);
             }
             ClientMessage::StartGame(start) => {
-                self.handle_start_game(start, ctx);
+                self.handle_start_game(*start, ctx);
             }
             ClientMessage::JoinGame(room_id) => {
                 self.handle_join_game(room_id, ctx);
@@ -530,13 +539,17 @@ enum GameState {
 struct TeamResult {
     score: f32,
     resigned: bool,
+    /// Lost before the game was scored, like by losing their king
+    lost: bool,
 }
 
 #[derive(Debug, Serialize)]
 struct GetGameResultResponse {
     state: GameState,
     teams: Option<Vec<TeamResult>>,
+    /// None for a draw
     winner: Option<usize>,
+    draw: bool,
 }
 
 async fn get_game_result(
@@ -555,43 +568,67 @@ async fn get_game_result(
     };
 
     let response = match &view.state {
-        GameStateView::Done(scoring) => {
-            let mut teams: Vec<_> = scoring
+        GameStateView::Done(done) => {
+            let mut teams: Vec<_> = done
+                .scoring
                 .scores
                 .iter()
                 .map(|&s| TeamResult {
                     score: s as f32 / 2.0,
                     resigned: false,
+                    lost: false,
                 })
                 .collect();
             for seat in &view.seats {
                 teams[seat.team.as_usize() - 1].resigned |= seat.resigned;
             }
+            for team in &done.scoring.lost {
+                teams[team.as_usize() - 1].lost = true;
+            }
             let mut winner = (0, 0.0);
             for (idx, team) in teams.iter().enumerate() {
-                if team.resigned {
+                if team.resigned || team.lost {
                     continue;
                 }
                 if team.score > winner.1 {
                     winner = (idx + 1, team.score);
                 }
             }
+            let draw = done.scoring.draw;
             GetGameResultResponse {
                 state: GameState::Done,
                 teams: Some(teams),
-                winner: Some(winner.0),
+                winner: if draw { None } else { Some(winner.0) },
+                draw,
             }
         }
         _ => GetGameResultResponse {
             state: GameState::Play,
             teams: None,
             winner: None,
+            draw: false,
         },
     };
 
     Ok(HttpResponse::Ok().json(response))
 }
 
+async fn get_game_record(
+    req: actix_web::HttpRequest,
+    server_addr: web::Data<Addr<GameServer>>,
+) -> actix_web::Result<HttpResponse> {
+    let room_id = req.match_info().get("id").unwrap().parse().unwrap();
+
+    let resp = server_addr.send(server::GetRecord { room_id }).await.unwrap();
+
+    match resp {
+        Ok(record) => Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(record)),
+        Err(_) => Ok(HttpResponse::BadRequest().body("Game record not available")),
+    }
+}
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
@@ -611,6 +648,7 @@ async fn main() -> std::io::Result<()> {
             .service(web::resource("/api/game/create").route(web::post().to(create_game)))
             .service(web::resource("/api/game/{id}").route(web::get().to(get_game_view)))
             .service(web::resource("/api/game/{id}/result").route(web::get().to(get_game_result)))
+            .service(web::resource("/api/game/{id}/record").route(web::get().to(get_game_record)))
     })
     .bind("0.0.0.0:8088")?
     .run()
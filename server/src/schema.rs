@@ -6,6 +6,7 @@ diesel::table! {
         name -> Text,
         replay -> Nullable<Bytea>,
         owner -> Nullable<Int8>,
+        snapshot -> Nullable<Bytea>,
     }
 }
 
@@ -69,7 +69,7 @@ pub struct Join {
 }
 
 impl actix::Message for Join {
-    type Result = Result<Addr<GameRoom>, ()>;
+    type Result = Result<Addr<GameRoom>, message::Error>;
 }
 
 #[derive(Message)]
@@ -121,6 +121,14 @@ impl actix::Message for GetAdminView {
     type Result = Result<game::GameView, ()>;
 }
 
+pub struct GetRecord {
+    pub room_id: u32,
+}
+
+impl actix::Message for GetRecord {
+    type Result = Result<String, ()>;
+}
+
 // Admin //////////////////////////////////////////////////////////////////////
 
 #[derive(Message)]
@@ -259,7 +267,7 @@ impl GameServer {
         &mut self,
         session_id: usize,
         room_id: u32,
-    ) -> impl ActorFuture<Self, Output = ()> {
+    ) -> impl ActorFuture<Self, Output = Result<(), message::Error>> {
         let session = self
             .sessions
             .get_mut(&session_id)
@@ -270,7 +278,7 @@ impl GameServer {
 
         let prefetch = if let Some(room_addr) = room_addr {
             session.room_ids.push(room_id);
-            Either::Right(async move { Ok::<_, ()>(room_addr) }.into_actor(self))
+            Either::Right(async move { Ok::<_, message::Error>(room_addr) }.into_actor(self))
         } else {
             Either::Left(
                 self.db
@@ -279,17 +287,21 @@ impl GameServer {
                     .then(move |res, act, ctx| {
                         let db_game = match res {
                             Ok(Ok(db_game)) => db_game,
-                            _ => return fut::err(()),
+                            _ => return fut::err(message::Error::other("Game not found")),
                         };
 
                         let replay = match db_game.replay {
                             Some(r) => r,
-                            _ => return fut::err(()),
+                            _ => return fut::err(message::Error::other("Game has no replay")),
                         };
 
-                        let game = match game::Game::load(&replay) {
-                            Some(r) => r,
-                            _ => return fut::err(()),
+                        let snapshot = db_game.snapshot.as_deref();
+                        let game = match game::Game::load_with_snapshot(&replay, snapshot) {
+                            Ok(r) => r,
+                            Err(error) => {
+                                println!("Game {} failed to load: {}", room_id, error);
+                                return fut::err(message::Error::GameLoad { room_id, error });
+                            }
                         };
 
                         let room = GameRoom {
@@ -303,6 +315,7 @@ impl GameServer {
                             db: act.db.clone(),
                             server: ctx.address(),
                             kicked_players: HashSet::new(),
+                            nicks: HashMap::new(),
                         };
 
                         let addr = room.start();
@@ -326,15 +339,14 @@ impl GameServer {
             )
         };
 
-        prefetch.then(move |res, act, _| {
-            if let Ok(room_addr) = res {
+        prefetch.then(move |res, _act, _| {
+            fut::ready(res.map(|room_addr| {
                 room_addr.do_send(game_room::Join {
                     session_id,
                     user_id,
                     addr,
                 });
-            }
-            async {}.into_actor(act)
+            }))
         })
     }
 }
@@ -429,7 +441,7 @@ impl Handler<ListRooms> for GameServer {
 
 /// Join room, send disconnect message to old room
 impl Handler<Join> for GameServer {
-    type Result = ActorResponse<Self, Result<Addr<GameRoom>, ()>>;
+    type Result = ActorResponse<Self, Result<Addr<GameRoom>, message::Error>>;
 
     fn handle(&mut self, msg: Join, _ctx: &mut Context<Self>) -> Self::Result {
         let Join {
@@ -440,12 +452,12 @@ impl Handler<Join> for GameServer {
 
         let session = match self.sessions.get(&id) {
             Some(x) => x,
-            None => return ActorResponse::reply(Err(())),
+            None => return ActorResponse::reply(Err(message::Error::other("No session"))),
         };
 
         match session.user_id {
             Some(x) => x,
-            None => return ActorResponse::reply(Err(())),
+            None => return ActorResponse::reply(Err(message::Error::other("Not identified"))),
         };
 
         let after_leave = if leave_previous {
@@ -456,11 +468,11 @@ impl Handler<Join> for GameServer {
 
         let result = after_leave
             .then(move |(), act, _ctx| act.join_room(id, room_id))
-            .then(move |(), act, _ctx| {
-                fut::ready(match act.rooms.get(&room_id) {
+            .then(move |res, act, _ctx| {
+                fut::ready(res.and_then(|()| match act.rooms.get(&room_id) {
                     Some(room) => Ok(room.addr.clone()),
-                    None => Err(()),
-                })
+                    None => Err(message::Error::other("Game not found")),
+                }))
             });
 
         ActorResponse::r#async(result)
@@ -530,10 +542,14 @@ impl Handler<CreateRoom> for GameServer {
 
         let komis = komis.as_slice().into();
         let seed = self.rng.next_u64();
-        let game = match game::Game::standard(&seats, komis, size, mods, seed) {
-            Some(g) => g,
-            None => return ActorResponse::reply(Err(Error::other("Rules not accepted"))),
+        let mut game = match game::Game::standard(&seats, komis, size, mods, seed) {
+            Ok(g) => g,
+            Err(errors) => return ActorResponse::reply(Err(Error::Rules(errors))),
         };
+        game.started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|d| d.as_secs());
 
         if user_id != 0 {
             let profile = self
@@ -570,6 +586,7 @@ impl Handler<CreateRoom> for GameServer {
                     .send(db::StoreGame {
                         id: None,
                         replay: None,
+                        snapshot: None,
                         name: cloned_name,
                         owner,
                     })
@@ -596,6 +613,7 @@ impl Handler<CreateRoom> for GameServer {
                     db: act.db.clone(),
                     server: ctx.address(),
                     kicked_players: HashSet::new(),
+                    nicks: HashMap::new(),
                 };
 
                 let addr = room.start();
@@ -615,7 +633,7 @@ impl Handler<CreateRoom> for GameServer {
                 } else {
                     Either::Right(
                         act.join_room(id, room_id)
-                            .then(move |(), _, _| fut::ready(Ok((room_id, Some(addr))))),
+                            .then(move |_, _, _| fut::ready(Ok((room_id, Some(addr))))),
                     )
                 })
             });
@@ -804,3 +822,24 @@ impl Handler<GetAdminView> for GameServer {
         ActorResponse::r#async(fut)
     }
 }
+
+impl Handler<GetRecord> for GameServer {
+    type Result = ActorResponse<Self, Result<String, ()>>;
+
+    fn handle(&mut self, msg: GetRecord, _ctx: &mut Self::Context) -> Self::Result {
+        let GetRecord { room_id } = msg;
+
+        let room = match self.rooms.get(&room_id) {
+            Some(x) => x,
+            None => return ActorResponse::reply(Err(())),
+        };
+        let fut = room.addr.send(game_room::GetRecord);
+
+        let fut = fut.into_actor(self).then(move |res, _act, _| match res {
+            Ok(Ok(record)) => fut::ok(record),
+            _ => fut::err(()),
+        });
+
+        ActorResponse::r#async(fut)
+    }
+}
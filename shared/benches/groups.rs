@@ -0,0 +1,71 @@
+//! Compares finding the groups from scratch to the group tracker, by playing random games
+//! with the capture checks a move needs.
+//!
+//!     cargo bench -p shared --bench groups
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::prelude::*;
+use rand_pcg::Lcg64Xsh32;
+
+use shared::game::{find_groups, Board, Color, Group, GroupTracker};
+
+const MOVES: usize = 300;
+
+/// Plays random moves for two colors, removing dead groups like a capture would.
+fn play(size: u32, mut dead_groups: impl FnMut(&Board) -> Vec<Group>) -> Board {
+    let mut rng = Lcg64Xsh32::seed_from_u64(size as u64);
+    let mut board: Board = Board::empty(size, size, false);
+
+    for turn in 0..MOVES {
+        let point = (rng.gen_range(0, size), rng.gen_range(0, size));
+        if !board.get_point(point).is_empty() {
+            continue;
+        }
+        let color = Color(turn as u8 % 2 + 1);
+        board.set_point(point, color);
+
+        // Opponents first, then the player's own groups
+        for own in &[false, true] {
+            for group in dead_groups(&board) {
+                if (group.team == color) == *own {
+                    for &point in &group.points {
+                        board.set_point(point, Color::empty());
+                    }
+                }
+            }
+        }
+    }
+
+    board
+}
+
+fn scratch(board: &Board) -> Vec<Group> {
+    find_groups(board)
+        .into_iter()
+        .filter(|g| g.liberties == 0)
+        .collect()
+}
+
+fn groups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("capture checks");
+    for &size in &[9, 19, 25] {
+        assert_eq!(play(size, scratch), {
+            let mut tracker = GroupTracker::default();
+            play(size, |board| tracker.dead_groups(board, None))
+        });
+
+        group.bench_with_input(BenchmarkId::new("find_groups", size), &size, |b, &size| {
+            b.iter(|| play(black_box(size), scratch))
+        });
+        group.bench_with_input(BenchmarkId::new("tracker", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut tracker = GroupTracker::default();
+                play(black_box(size), |board| tracker.dead_groups(board, None))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, groups);
+criterion_main!(benches);
@@ -0,0 +1,103 @@
+//! Memory the board history of a room takes, compared to keeping every position whole, and
+//! the time it takes to look at old positions.
+//!
+//!     cargo bench -p shared --bench history
+
+use std::mem::{size_of, size_of_val};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::prelude::*;
+use rand_pcg::Lcg64Xsh32;
+
+use shared::game::clock::Millisecond;
+use shared::game::{ActionKind, Color, Game, GameModifier, GroupVec, PhantomGo, TraitorGo};
+
+const MOVES: usize = 300;
+
+/// A game of up to `MOVES` random moves.
+fn play(size: u8, mods: GameModifier) -> Game {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (size, size), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let mut rng = Lcg64Xsh32::seed_from_u64(size as u64);
+    let size = size as u32;
+    // Small boards may run out of legal moves first
+    for _ in 0..MOVES * 20 {
+        if game.shared.board_history.len() > MOVES {
+            break;
+        }
+        let player = [100, 200][game.shared.turn];
+        let (x, y) = (rng.gen_range(0, size), rng.gen_range(0, size));
+        let _ = game.make_action(player, ActionKind::Place(x, y), Millisecond(0));
+    }
+    game
+}
+
+/// Bytes the history would take with a whole board for every move.
+fn whole_size(game: &Game) -> usize {
+    game.shared
+        .board_history
+        .iter()
+        .map(|history| {
+            let visibility = history.board_visibility.as_ref();
+            size_of::<shared::game::BoardHistory>()
+                + history.board.points.len() * size_of::<Color>()
+                + visibility.map_or(0, |v| v.points.len() * size_of_val(&v.points[0]))
+        })
+        .sum()
+}
+
+fn games() -> Vec<(String, Game)> {
+    let phantom = GameModifier {
+        phantom: Some(PhantomGo {}),
+        ..GameModifier::default()
+    };
+    let mut games = Vec::new();
+    for &size in &[9, 19, 25] {
+        games.push((format!("{}", size), play(size, GameModifier::default())));
+        games.push((format!("{} phantom", size), play(size, phantom.clone())));
+    }
+    let traitor = GameModifier {
+        traitor: Some(TraitorGo { traitor_count: 20 }),
+        ..GameModifier::default()
+    };
+    games.push(("19 traitor".to_string(), play(19, traitor)));
+    games
+}
+
+fn history(c: &mut Criterion) {
+    let games = games();
+
+    println!("Board history:");
+    for (name, game) in &games {
+        let whole = whole_size(game);
+        let stored = game.shared.board_history.memory_size();
+        let state = game.shared.board_history.state_memory_size();
+        println!(
+            "  {:>12}, {:>3} moves: {:>7} bytes ({:>2}% game state), {:>7} with whole boards, {:>7} saved per room",
+            name,
+            game.shared.board_history.len() - 1,
+            stored,
+            state * 100 / stored,
+            whole,
+            whole as isize - stored as isize
+        );
+    }
+
+    let mut group = c.benchmark_group("history views");
+    for (name, game) in &games {
+        group.bench_with_input(BenchmarkId::new("get_view_at", name), game, |b, game| {
+            b.iter(|| {
+                for turn in 0..game.shared.board_history.len() as u32 {
+                    black_box(game.get_view_at(100, turn));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, history);
+criterion_main!(benches);
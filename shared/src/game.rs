@@ -1,21 +1,33 @@
 mod board;
 pub mod clock;
 pub mod export;
+pub mod fairness;
+pub mod groups;
+pub mod history;
+pub mod record;
+pub mod setup;
+pub mod sgf;
+pub mod snapshot;
 #[cfg(test)]
 mod tests;
 
 use clock::{ClockRule, GameClock, Millisecond};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashSet, VecDeque};
+use std::fmt;
 
 use bitmaps::Bitmap;
 use tinyvec::TinyVec;
 
+use crate::states::play::starting_stones;
 use crate::states::play::traitor::TraitorState;
+use crate::states::rules;
 pub use crate::states::GameState;
-use crate::states::PlayState;
 use crate::states::ScoringState;
 pub use board::{Board, Point};
+pub use groups::GroupTracker;
+pub use history::History;
+pub use setup::SetupError;
 
 ///////////////////////////////////////////////////////////////////////////////
 //                                    Data                                   //
@@ -52,12 +64,22 @@ impl Color {
         self.0 as usize
     }
 
+    /// Neutral stones don't belong to any team. They never own territory.
+    pub const fn neutral() -> Color {
+        Color(5)
+    }
+
+    pub const fn is_neutral(self) -> bool {
+        self.0 == 5
+    }
+
     pub fn name(item: impl Into<Color>) -> &'static str {
         match item.into().0 {
             1 => "Black",
             2 => "White",
             3 => "Blue",
             4 => "Red",
+            5 => "Neutral",
             _ => "???",
         }
     }
@@ -77,7 +99,7 @@ impl From<u8> for Color {
 
 // Seat ///////////////////////////////////////////////////////////////////////
 
-#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Seat {
     pub player: Option<u64>,
     pub team: Color,
@@ -114,6 +136,11 @@ pub enum ActionKind {
     Pass,
     Cancel,
     Resign,
+    /// Place a shape in shape go. `Place` is the same as choosing the first shape unrotated.
+    PlaceShape(u32, u32, ShapeChoice),
+    /// Offer to end the game as a draw. Can be done on any turn, and the game ends once every
+    /// player agrees before the next stone is placed.
+    ProposeDraw,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -121,6 +148,8 @@ pub enum ReplayActionKind {
     Play(ActionKind),
     TakeSeat(u32),
     LeaveSeat(u32),
+    /// The player on turn ran out of time and resigned.
+    Timeout,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -145,6 +174,8 @@ impl GameAction {
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct ZenGo {
+    /// Colors the players rotate through, one per move.
+    /// Zero takes the number of komis, any other value has to match it.
     pub color_count: u8,
 }
 
@@ -156,10 +187,82 @@ pub struct HiddenMoveGo {
 
 /// Visibility modes describe how the game state should be displayed, without
 /// affecting the actual gameplay in any way.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum VisibilityMode {
     /// Display all stones as the same color for both players.
-    OneColor,
+    OneColor(OneColorChoice),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OneColorChoice {
+    Fixed(Color),
+    /// One of the team colors, picked from the game seed.
+    Random,
+}
+
+impl OneColorChoice {
+    pub fn color(self, team_count: usize, seed: u64) -> Color {
+        use rand::Rng;
+
+        /// Separate stream from the other random modifiers.
+        const STREAM: u64 = 0x0c01_0c01_0c01_0c01;
+
+        match self {
+            OneColorChoice::Fixed(color) => color,
+            OneColorChoice::Random => {
+                let mut rng = rand_pcg::Lcg64Xsh32::new(seed, STREAM);
+                Color(rng.gen_range(0, team_count.max(1)) as u8 + 1)
+            }
+        }
+    }
+}
+
+/// One color go used to be a unit variant that always showed white stones, so old
+/// replays have just the variant name or index here.
+impl<'de> Deserialize<'de> for VisibilityMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, MapAccess, Visitor};
+
+        const LEGACY: VisibilityMode = VisibilityMode::OneColor(OneColorChoice::Fixed(Color(2)));
+
+        #[derive(Deserialize)]
+        enum Variant {
+            OneColor,
+        }
+
+        struct ModeVisitor;
+
+        impl<'de> Visitor<'de> for ModeVisitor {
+            type Value = VisibilityMode;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a visibility mode")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                match v {
+                    0 => Ok(LEGACY),
+                    _ => Err(E::invalid_value(de::Unexpected::Unsigned(v), &self)),
+                }
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                match v {
+                    "OneColor" => Ok(LEGACY),
+                    _ => Err(E::unknown_variant(v, &["OneColor"])),
+                }
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                match map.next_key::<Variant>()? {
+                    Some(Variant::OneColor) => Ok(VisibilityMode::OneColor(map.next_value()?)),
+                    None => Err(de::Error::invalid_length(0, &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(ModeVisitor)
+    }
 }
 
 /// Based on the 4+1 variant where a player gets an extra turn if they make
@@ -193,6 +296,185 @@ pub struct TraitorGo {
     pub traitor_count: u32,
 }
 
+/// Neutral stones are scattered on the board before the game starts.
+/// They belong to no team and act as walls for everyone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NeutralStones {
+    pub count: u32,
+    /// If true, any team can capture neutral stones. Otherwise they stay on the
+    /// board even without liberties.
+    pub capturable: bool,
+}
+
+/// Every team starts with stones placed on random points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RandomStart {
+    pub stones_per_team: u32,
+}
+
+/// Teams are knocked out when they run out of stones. The last team standing wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastSurvivor {
+    /// Nobody is knocked out during the first moves of the game.
+    pub grace_moves: u32,
+    /// Teams with fewer stones on the board than this are knocked out.
+    pub min_stones: u32,
+}
+
+/// How many players of a rengo team have to resign before the whole team is out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TeamResign {
+    Any,
+    Majority,
+    All,
+}
+
+impl TeamResign {
+    fn reached(self, votes: usize, members: usize) -> bool {
+        match self {
+            TeamResign::Any => votes > 0,
+            TeamResign::Majority => votes * 2 > members,
+            TeamResign::All => votes == members,
+        }
+    }
+}
+
+/// The game goes to scoring once this many moves have been played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveLimit {
+    pub moves: u32,
+}
+
+/// The game starts from a given position instead of an empty board, e.g. one loaded from SGF.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StartPosition {
+    pub size: (u8, u8),
+    /// Color of every point, row by row. 0 is empty.
+    pub points: Vec<u8>,
+    /// The color that moves first.
+    pub to_move: u8,
+}
+
+/// Teams in the same alliance share liberties and can't capture each other.
+/// Scores are still counted separately for every team.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alliances {
+    /// Alliance number for each team, in team order.
+    pub teams: Vec<u8>,
+}
+
+impl Alliances {
+    pub fn allied(&self, a: Color, b: Color) -> bool {
+        if a == b {
+            return true;
+        }
+        let alliance = |c: Color| {
+            c.as_usize()
+                .checked_sub(1)
+                .and_then(|idx| self.teams.get(idx))
+        };
+        match (alliance(a), alliance(b)) {
+            (Some(x), Some(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+/// The first stone each team places is its king. A team whose king is captured is out.
+/// With hidden stones, only the owning team can see where its king is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KingGo {}
+
+/// A polyomino given as offsets from the point it's placed on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shape {
+    pub points: Vec<(i8, i8)>,
+}
+
+/// Every move places one of the given shapes instead of a single stone.
+/// Overlapping existing stones are ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeGo {
+    pub shapes: Vec<Shape>,
+    pub allow_rotation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ShapeChoice {
+    pub shape: u32,
+    /// Number of clockwise quarter turns
+    pub rotation: u8,
+}
+
+impl ShapeGo {
+    /// The 2x2 blob of pixel go.
+    /// Coordinates point at the corner between the stones, so 0,0 is outside the board.
+    pub fn pixel() -> ShapeGo {
+        ShapeGo {
+            shapes: vec![Shape {
+                points: vec![(-1, -1), (0, -1), (-1, 0), (0, 0)],
+            }],
+            allow_rotation: false,
+        }
+    }
+
+    /// Pixel go places shapes on the corners between points rather than on the points.
+    pub fn is_pixel(&self) -> bool {
+        *self == ShapeGo::pixel()
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.shapes
+            .iter()
+            .map(|s| s.points.len())
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Offsets of the chosen shape after rotating it.
+    /// Returns None if the choice is not valid for this game.
+    pub fn offsets(&self, choice: ShapeChoice) -> Option<GroupVec<(i32, i32)>> {
+        let shape = self.shapes.get(choice.shape as usize)?;
+        if choice.rotation > 0 && (!self.allow_rotation || choice.rotation > 3) {
+            return None;
+        }
+
+        let offsets = shape
+            .points
+            .iter()
+            .map(|&(dx, dy)| {
+                let (mut dx, mut dy) = (dx as i32, dy as i32);
+                for _ in 0..choice.rotation {
+                    let (nx, ny) = (-dy, dx);
+                    dx = nx;
+                    dy = ny;
+                }
+                (dx, dy)
+            })
+            .collect();
+        Some(offsets)
+    }
+
+    /// Board points covered by the shape, skipping points that fall outside of the board.
+    /// Returns None if the choice is not valid for this game.
+    pub fn points<T: Copy + Default>(
+        &self,
+        board: &Board<T>,
+        (x, y): Point,
+        choice: ShapeChoice,
+    ) -> Option<GroupVec<Point>> {
+        let mut points = GroupVec::new();
+        for (dx, dy) in self.offsets(choice)? {
+            if let Some(p) = board.wrap_point(x as i32 + dx, y as i32 + dy) {
+                if !points.contains(&p) {
+                    points.push(p);
+                }
+            }
+        }
+        Some(points)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct GameModifier {
     /// Pixel go is a game mode where you place 2x2 blobs instead of a single stone.
@@ -247,6 +529,47 @@ pub struct GameModifier {
 
     #[serde(default)]
     pub no_undo: bool,
+
+    #[serde(default)]
+    pub neutral_stones: Option<NeutralStones>,
+
+    #[serde(default)]
+    pub random_start: Option<RandomStart>,
+
+    /// Only for games with three or more teams.
+    #[serde(default)]
+    pub last_survivor: Option<LastSurvivor>,
+
+    #[serde(default)]
+    pub alliances: Option<Alliances>,
+
+    #[serde(default)]
+    pub king: Option<KingGo>,
+
+    /// Generalized pixel go. Can't be used together with `pixel`.
+    #[serde(default)]
+    pub shape: Option<ShapeGo>,
+
+    #[serde(default)]
+    pub move_limit: Option<MoveLimit>,
+
+    /// Without this, resigning only takes the player's own seat out of the game.
+    #[serde(default)]
+    pub team_resign: Option<TeamResign>,
+
+    #[serde(default)]
+    pub start_position: Option<StartPosition>,
+}
+
+impl GameModifier {
+    /// The shapes placed on every move, if any. Pixel go is the 2x2 preset.
+    pub fn shape_rule(&self) -> Option<ShapeGo> {
+        if self.pixel {
+            Some(ShapeGo::pixel())
+        } else {
+            self.shape.clone()
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -262,6 +585,9 @@ pub struct BoardHistory {
     pub points: GroupVec<i32>,
     pub turn: usize,
     pub traitor: Option<TraitorState>,
+    pub eliminated: GroupVec<Color>,
+    pub kings: GroupVec<Option<Point>>,
+    pub resign_votes: GroupVec<bool>,
 }
 
 #[derive(Clone)]
@@ -272,11 +598,21 @@ pub struct SharedState {
     pub pass_count: usize,
     pub board: Board,
     pub board_visibility: Option<VisibilityBoard>,
-    pub board_history: Vec<BoardHistory>,
+    pub board_history: History,
     pub komis: GroupVec<i32>,
     pub mods: GameModifier,
     pub clock: Option<GameClock>,
     pub traitor: Option<TraitorState>,
+    /// Teams knocked out in last survivor or king mode
+    pub eliminated: GroupVec<Color>,
+    /// King stone of each team in king mode
+    pub kings: GroupVec<Option<Point>>,
+    /// Seats that voted to resign their team
+    pub resign_votes: GroupVec<bool>,
+    /// Groups of the stones on `board`
+    pub groups: GroupTracker,
+    /// Replay version of the rules the game is played with, see [`REPLAY_VERSION`].
+    pub version: u32,
 }
 
 #[derive(Clone)]
@@ -286,6 +622,8 @@ pub struct Game {
     pub shared: SharedState,
     pub actions: Vec<GameAction>,
     pub seed: u64,
+    /// Unix time in seconds when the game was created. Unknown for older games.
+    pub started: Option<u64>,
 }
 
 impl SharedState {
@@ -295,6 +633,55 @@ impl SharedState {
             .expect("Game turn number invalid")
             .clone()
     }
+
+    /// Resigns the given seats. With `team_resign` the seats vote instead, and every seat of a
+    /// team resigns together once enough of the team has voted.
+    pub(crate) fn resign_seats(&mut self, seats: &[usize]) {
+        let rule = match self.mods.team_resign {
+            Some(rule) => rule,
+            None => {
+                for &idx in seats {
+                    self.seats[idx].resigned = true;
+                }
+                return;
+            }
+        };
+
+        for &idx in seats {
+            self.resign_votes[idx] = true;
+        }
+
+        let teams = seats
+            .iter()
+            .map(|&idx| self.seats[idx].team)
+            .collect::<GroupVec<_>>();
+        for team in teams {
+            let votes = self
+                .seats
+                .iter()
+                .zip(&self.resign_votes)
+                .filter(|(seat, &vote)| seat.team == team && vote)
+                .count();
+            let members = self.seats.iter().filter(|seat| seat.team == team).count();
+
+            if rule.reached(votes, members) {
+                for seat in &mut self.seats {
+                    if seat.team == team {
+                        seat.resigned = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether at most one team still has seats in play.
+    pub(crate) fn one_team_left(&self) -> bool {
+        let mut teams = self.seats.iter().filter(|s| !s.resigned).map(|s| s.team);
+        match teams.next() {
+            Some(first) => teams.all(|team| team == first),
+            None => true,
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -338,31 +725,93 @@ pub struct FreePlacementView {
     pub players_ready: Vec<bool>,
 }
 
+/// What a player can see of `PlayState`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayView {
+    pub players_passed: Vec<bool>,
+    pub draw_proposed: Vec<bool>,
+    /// Only the stones the viewer can see on their board
+    pub last_stone: Option<GroupVec<(u32, u32)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DoneView {
+    pub scoring: ScoringState,
+    /// The random seed of the game, revealed to check against the commitment
+    pub seed: u64,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum GameStateView {
     FreePlacement(FreePlacementView),
-    Play(PlayState),
+    Play(PlayView),
+    /// The whole board is revealed for scoring, so the state is sent as is.
+    /// See `GameStateView::new` for why each field is safe to show.
     Scoring(ScoringState),
-    Done(ScoringState),
+    Done(DoneView),
 }
 
-impl From<GameState> for GameStateView {
-    fn from(state: GameState) -> Self {
+impl GameStateView {
+    /// `board` is the board as the viewer sees it.
+    fn new(state: GameState, board: &[Color], width: u32, seed: u64) -> Self {
         match state {
             GameState::FreePlacement(state) => GameStateView::FreePlacement(FreePlacementView {
                 players_ready: state.players_ready,
             }),
-            GameState::Play(state) => GameStateView::Play(state),
-            GameState::Scoring(state) => GameStateView::Scoring(state),
-            GameState::Done(state) => GameStateView::Done(state),
+            GameState::Play(state) => GameStateView::Play(PlayView {
+                players_passed: state.players_passed,
+                draw_proposed: state.draw_proposed,
+                last_stone: visible_stones(state.last_stone, board, width),
+            }),
+            GameState::Scoring(state) => {
+                // Destructured so a new field has to be checked here before it's sent.
+                let ScoringState {
+                    // Every stone on the board, which the viewer sees in full while scoring
+                    groups,
+                    // Territory counted from those same groups
+                    points,
+                    // That territory added to the public `GameView::points`
+                    scores,
+                    // Only whether each seat accepted the marking
+                    players_accepted,
+                    draw,
+                    // Knocked out teams, already public through their seats
+                    lost,
+                } = state;
+                GameStateView::Scoring(ScoringState {
+                    groups,
+                    points,
+                    scores,
+                    players_accepted,
+                    draw,
+                    lost,
+                })
+            }
+            GameState::Done(scoring) => GameStateView::Done(DoneView { scoring, seed }),
         }
     }
 }
 
+/// Drops the points that are empty on the viewer's board, so a move marker
+/// can't give away a hidden stone.
+fn visible_stones(
+    points: Option<GroupVec<Point>>,
+    board: &[Color],
+    width: u32,
+) -> Option<GroupVec<Point>> {
+    let points = points?
+        .into_iter()
+        .filter(|&(x, y)| !board[(y * width + x) as usize].is_empty())
+        .collect::<GroupVec<_>>();
+    if points.is_empty() {
+        None
+    } else {
+        Some(points)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct GameView {
-    // TODO: we need a separate state view since we have hidden information
-    // currently players can cheat :F
     pub state: GameStateView,
     pub seats: GroupVec<Seat>,
     pub turn: u32,
@@ -374,6 +823,13 @@ pub struct GameView {
     pub points: GroupVec<i32>,
     pub move_number: u32,
     pub clock: Option<GameClock>,
+    /// King stone of each team, if it's visible to the viewer
+    pub kings: GroupVec<Option<Point>>,
+    /// SHA-256 of the random seed, see [`fairness`]
+    pub seed_commitment: String,
+    /// Seats that voted to resign with `team_resign`. `seats` only shows a seat as resigned
+    /// once enough of its team has voted.
+    pub resign_votes: GroupVec<bool>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -384,6 +840,22 @@ pub struct GameHistory {
     pub move_number: u32,
 }
 
+/// Version of the rules that replays are played back with.
+///
+/// Bump this whenever a rule change would make old replays play out differently, and
+/// keep the old behaviour for games with a lower `Game::version`.
+///
+/// - 0: Replays from before versioning. Their setups were only checked against the limits
+///   of the time, so seats can play any of 4 colors. Phantom go only reveals groups with one
+///   liberty, even in pixel go.
+/// - 1: Setups are validated when the game is created. Phantom go reveals every group a
+///   single placement can capture.
+///
+/// Other rule changes since only touch modifiers that version 0 games can't have. Zen go
+/// games start from the first color in every version, since version 0 replays stored the
+/// seat colors of the last move instead of the first.
+pub const REPLAY_VERSION: u32 = 1;
+
 #[derive(Serialize, Deserialize)]
 struct GameReplay {
     actions: Vec<GameAction>,
@@ -393,6 +865,35 @@ struct GameReplay {
     size: (u8, u8),
     #[serde(default)]
     seed: u64,
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    started: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LoadError {
+    /// The data is not a game
+    Decode(String),
+    /// Saved by a newer version of the server
+    Version(u32),
+    Setup(Vec<SetupError>),
+    /// The action with this index can't be played
+    Action(usize),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Decode(error) => write!(f, "Not a game: {}", error),
+            LoadError::Version(version) => write!(f, "Version {} is not supported", version),
+            LoadError::Setup(errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "Invalid game setup: {}", errors.join(", "))
+            }
+            LoadError::Action(idx) => write!(f, "Action {} can't be played", idx + 1),
+        }
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -406,23 +907,38 @@ impl Game {
         size: (u8, u8),
         mods: GameModifier,
         seed: u64,
-    ) -> Option<Game> {
-        if !seats.iter().all(|&t| t > 0 && t <= 4) {
-            return None;
-        }
+    ) -> Result<Game, Vec<SetupError>> {
+        setup::validate(seats, komis.len(), size, &mods)?;
+        Ok(Game::build(seats, komis, size, mods, seed, REPLAY_VERSION))
+    }
 
-        // 7 = 3 colors, rengo
-        // 4 = 4 colors
-        if !(1..=7).contains(&seats.len()) || !(1..=4).contains(&komis.len()) {
-            return None;
+    /// Creates the game without checking the setup.
+    fn build(
+        seats: &[u8],
+        komis: GroupVec<i32>,
+        size: (u8, u8),
+        mods: GameModifier,
+        seed: u64,
+        version: u32,
+    ) -> Game {
+        let mut mods = mods;
+        if let Some(zen) = &mut mods.zen_go {
+            // Old games keep the color count they were played with
+            if zen.color_count == 0 {
+                zen.color_count = komis.len() as u8;
+            }
         }
 
-        // Don't allow huge boards
-        if size.0 > 25 || size.1 > 25 {
-            return None;
+        let mut board = Board::empty(size.0 as _, size.1 as _, mods.toroidal.is_some());
+        if let Some(position) = &mods.start_position {
+            for (idx, &color) in position.points.iter().enumerate() {
+                if let Some(point) = board.idx_to_coord(idx) {
+                    board.set_point(point, Color(color));
+                }
+            }
         }
+        starting_stones::place(&mut board, komis.len(), seed, &mods);
 
-        let board = Board::empty(size.0 as _, size.1 as _, mods.toroidal.is_some());
         let state = if let Some(rules) = &mods.hidden_move {
             GameState::free_placement(
                 seats.len(),
@@ -462,73 +978,224 @@ impl Game {
 
         let traitor = mods.traitor.as_ref().map(|rule| {
             let mut stone_count = (size.0 as usize * size.1 as usize / komis.len()) as u32;
-            if mods.pixel {
-                stone_count /= 4;
+            if let Some(rule) = mods.shape_rule() {
+                stone_count /= rule.max_size() as u32;
             }
 
             TraitorState::new(komis.len(), stone_count, seed, rule)
         });
 
-        Some(Game {
+        // The first seat playing the color to move starts
+        let turn = mods
+            .start_position
+            .as_ref()
+            .and_then(|p| seats.iter().position(|&t| t == p.to_move))
+            .unwrap_or(0);
+
+        let kings = if mods.king.is_some() {
+            komis.iter().map(|_| None).collect()
+        } else {
+            GroupVec::new()
+        };
+
+        Game {
             state,
             state_stack: Vec::new(),
             shared: SharedState {
-                seats: seats.iter().map(|&t| Seat::new(Color(t))).collect(),
+                seats: seats
+                    .iter()
+                    .map(|&t| match mods.zen_go {
+                        // Everyone starts with the first color
+                        Some(_) => Seat::new(Color(1)),
+                        None => Seat::new(Color(t)),
+                    })
+                    .collect(),
                 points: komis.clone(),
-                turn: 0,
+                turn,
                 pass_count: 0,
                 board: board.clone(),
                 board_visibility: board_visibility.clone(),
-                board_history: vec![BoardHistory {
+                board_history: History::new(BoardHistory {
                     hash: board.hash(),
                     board,
                     board_visibility,
                     state: GameState::play(seats.len()),
                     points: komis.clone(),
-                    turn: 0,
+                    turn,
                     traitor: traitor.clone(),
-                }],
+                    eliminated: GroupVec::new(),
+                    kings: kings.clone(),
+                    resign_votes: seats.iter().map(|_| false).collect(),
+                }),
                 komis,
                 mods,
                 clock,
                 traitor,
+                eliminated: GroupVec::new(),
+                kings,
+                resign_votes: seats.iter().map(|_| false).collect(),
+                groups: GroupTracker::default(),
+                version,
             },
             actions: vec![],
             seed,
-        })
+            started: None,
+        }
     }
 
-    /// Loads a game from a replay dump. Can fail at any point due to changed rules...
-    /// Such is life.
-    pub fn load(dump: &[u8]) -> Option<Game> {
-        let mut replay: GameReplay = serde_cbor::from_slice(dump).ok()?;
+    /// Loads a game from a replay dump, playing it by the rules of its version.
+    pub fn load(dump: &[u8]) -> Result<Game, LoadError> {
+        let mut replay: GameReplay =
+            serde_cbor::from_slice(dump).map_err(|e| LoadError::Decode(e.to_string()))?;
         // TODO: PUZZLE make replays conserve clocks
         replay.mods.clock = None;
-        let mut game = Game::standard(
+        let mut game = Game::replay(
             &replay.seats,
             replay.komis,
             replay.size,
             replay.mods,
             replay.seed,
+            replay.version,
+            replay.actions,
         )?;
+        game.started = replay.started;
+        Ok(game)
+    }
+
+    /// Creates a game of the given version and plays the actions on it.
+    fn replay(
+        seats: &[u8],
+        komis: GroupVec<i32>,
+        size: (u8, u8),
+        mods: GameModifier,
+        seed: u64,
+        version: u32,
+        actions: Vec<GameAction>,
+    ) -> Result<Game, LoadError> {
+        if version > REPLAY_VERSION {
+            return Err(LoadError::Version(version));
+        }
+
+        let valid = if version == 0 {
+            setup::validate_legacy(seats, komis.len(), size)
+        } else {
+            setup::validate(seats, komis.len(), size, &mods)
+        };
+        valid.map_err(LoadError::Setup)?;
+
+        let mut game = Game::build(seats, komis, size, mods, seed, version);
+        game.apply_actions(actions).map_err(LoadError::Action)?;
+        Ok(game)
+    }
 
-        for action in replay.actions {
+    /// Plays recorded actions in order. Fails with the index of the first action that
+    /// doesn't apply.
+    fn apply_actions(&mut self, actions: Vec<GameAction>) -> Result<(), usize> {
+        for (idx, action) in actions.into_iter().enumerate() {
             use ReplayActionKind::*;
-            match action.action {
-                TakeSeat(seat_id) => {
-                    game.take_seat(action.user_id, seat_id as _).ok()?;
-                }
-                LeaveSeat(seat_id) => {
-                    game.leave_seat(action.user_id, seat_id as _).ok()?;
+            let ok = match action.action {
+                TakeSeat(seat_id) => self.take_seat(action.user_id, seat_id as _).is_ok(),
+                LeaveSeat(seat_id) => self.leave_seat(action.user_id, seat_id as _).is_ok(),
+                Play(play) => self
+                    .make_action(action.user_id, play, Millisecond(0))
+                    .is_ok(),
+                Timeout => self
+                    .play_action(action.user_id, ActionKind::Resign, Millisecond(0), true)
+                    .is_ok(),
+            };
+            if !ok {
+                return Err(idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// Problems in the game state that playing by the rules should never cause.
+    /// Used to check that stored games still load correctly.
+    pub fn consistency_errors(&self) -> Vec<String> {
+        let shared = &self.shared;
+        let team_count = shared.komis.len();
+        let mut errors = Vec::new();
+
+        if shared.turn >= shared.seats.len() {
+            errors.push(format!(
+                "turn {} but {} seats",
+                shared.turn,
+                shared.seats.len()
+            ));
+        }
+        if shared.points.len() != team_count {
+            errors.push(format!(
+                "{} scores for {} colors",
+                shared.points.len(),
+                team_count
+            ));
+        }
+        if let GameState::Scoring(scoring) | GameState::Done(scoring) = &self.state {
+            if scoring.scores.len() != team_count {
+                errors.push(format!(
+                    "{} final scores for {} colors",
+                    scoring.scores.len(),
+                    team_count
+                ));
+            }
+        }
+
+        for (idx, history) in shared.board_history.iter().enumerate() {
+            if history.hash != history.board.full_hash() {
+                errors.push(format!("board hash of move {} doesn't match", idx));
+            }
+        }
+        match shared.board_history.last() {
+            Some(last) if matches!(self.state, GameState::Play(_)) => {
+                if last.board != shared.board {
+                    errors.push("board differs from the last move".to_string());
                 }
-                Play(play) => {
-                    game.make_action(action.user_id, play, Millisecond(0))
-                        .ok()?;
+                if last.turn != shared.turn {
+                    errors.push("turn differs from the last move".to_string());
                 }
             }
+            Some(_) => {}
+            None => errors.push("no board history".to_string()),
+        }
+
+        // Version 0 seats could play any of 4 colors, whatever the komis
+        let color_count = match shared.version {
+            0 => 4,
+            _ => team_count,
+        };
+        let bad_point = shared
+            .board
+            .points
+            .iter()
+            .find(|c| c.as_usize() > color_count && !c.is_neutral());
+        if let Some(color) = bad_point {
+            errors.push(format!("stone of color {} on the board", color.0));
+        }
+
+        let alliances = shared.mods.alliances.as_ref();
+        let sorted = |groups: Vec<Group>| {
+            let mut groups = groups
+                .into_iter()
+                .map(|g| {
+                    let mut points = g.points.to_vec();
+                    points.sort_unstable();
+                    (points, g.liberties)
+                })
+                .collect::<Vec<_>>();
+            groups.sort_unstable();
+            groups
+        };
+        let tracked = shared.groups.clone().groups(&shared.board, alliances);
+        let found = find_groups_by(&shared.board, |a, b| match alliances {
+            Some(alliances) => alliances.allied(a, b),
+            None => a == b,
+        });
+        if sorted(tracked) != sorted(found) {
+            errors.push("tracked groups differ from the board".to_string());
         }
 
-        Some(game)
+        errors
     }
 
     /// Dumps the game to a (hopefully somewhat) stable replay format.
@@ -541,6 +1208,8 @@ impl Game {
             seats: shared.seats.iter().map(|x| x.team.0).collect(),
             mods: shared.mods.clone(),
             seed: self.seed,
+            version: shared.version,
+            started: self.started,
         };
 
         let mut vec = Vec::new();
@@ -593,10 +1262,22 @@ impl Game {
     }
 
     pub fn make_action(
+        &mut self,
+        player_id: u64,
+        action: ActionKind,
+        time: Millisecond,
+    ) -> Result<(), MakeActionError> {
+        self.play_action(player_id, action, time, false)
+    }
+
+    /// Makes the action, or resigns for the player on turn if `timed_out` or their clock
+    /// ran out.
+    fn play_action(
         &mut self,
         player_id: u64,
         mut action: ActionKind,
         time: Millisecond,
+        mut timed_out: bool,
     ) -> Result<(), MakeActionError> {
         if !self
             .shared
@@ -611,6 +1292,17 @@ impl Game {
             GameState::FreePlacement(state) => {
                 state.make_action(&mut self.shared, player_id, action.clone())
             }
+            // Draw offers don't take a turn, so they leave the clock alone.
+            GameState::Play(state) if action == ActionKind::ProposeDraw => {
+                state.make_action(&mut self.shared, player_id, action.clone())
+            }
+            // Nor do resign votes from players who aren't on turn.
+            GameState::Play(state)
+                if action == ActionKind::Resign
+                    && self.shared.seats[self.shared.turn].player != Some(player_id) =>
+            {
+                state.make_action(&mut self.shared, player_id, action.clone())
+            }
             GameState::Play(state) => {
                 let seat_idx = self.shared.turn;
                 // We want to keep the clock reset until all players have made a move.
@@ -628,6 +1320,9 @@ impl Game {
                 };
 
                 if time_left.0 < -2000 {
+                    timed_out = true;
+                }
+                if timed_out {
                     action = ActionKind::Resign;
                 }
 
@@ -669,7 +1364,11 @@ impl Game {
                     ActionChange::None => {}
                 }
 
-                self.actions.push(GameAction::play(player_id, action));
+                self.actions.push(if timed_out {
+                    GameAction::new(player_id, ReplayActionKind::Timeout)
+                } else {
+                    GameAction::play(player_id, action)
+                });
 
                 Ok(())
             }
@@ -711,14 +1410,16 @@ impl Game {
                 let mut board = board.points.clone();
                 let board_visibility = board_visibility.clone();
 
-                let one_color = matches!(
-                    self.shared.mods.visibility_mode,
-                    Some(VisibilityMode::OneColor)
-                );
-
-                // Set color to white.
-                // TODO: Change this to black once the client supports selecting the color
-                const ONE_COLOR_TEAM: Color = Color(2);
+                let one_color_team =
+                    self.shared
+                        .mods
+                        .visibility_mode
+                        .as_ref()
+                        .map(|mode| match mode {
+                            VisibilityMode::OneColor(choice) => {
+                                choice.color(shared.points.len(), self.seed)
+                            }
+                        });
 
                 let seat = shared.seats.iter().find(|x| x.player == Some(player_id));
 
@@ -729,20 +1430,16 @@ impl Game {
                     return (board, board_visibility.map(|x| x.points), 0);
                 }
 
-                if one_color {
+                if let Some(one_color_team) = one_color_team {
                     for p in &mut board {
                         if !p.is_empty() {
-                            *p = ONE_COLOR_TEAM;
+                            *p = one_color_team;
                         }
                     }
                 };
 
                 if let Some(active_seat) = seat {
-                    let team = if !one_color {
-                        active_seat.team
-                    } else {
-                        ONE_COLOR_TEAM
-                    };
+                    let team = one_color_team.unwrap_or(active_seat.team);
 
                     if let Some(mut visibility) = board_visibility {
                         let mut hidden_stones_left = 0;
@@ -792,8 +1489,9 @@ impl Game {
             &shared.board_visibility,
             game_done,
         );
-        GameView {
-            state: self.state.clone().into(),
+        let width = shared.board.width;
+        let mut view = GameView {
+            state: GameStateView::new(self.state.clone(), &board, width, self.seed),
             seats: shared.seats.clone(),
             turn: shared.turn as _,
             board,
@@ -816,37 +1514,59 @@ impl Game {
             } else {
                 None
             },
+            kings: shared.kings.clone(),
+            seed_commitment: fairness::seed_commitment(self.seed),
+            resign_votes: shared.resign_votes.clone(),
+        };
+
+        for rule in rules::rules(&shared.mods) {
+            rule.on_view(shared, player_id, &mut view);
         }
+
+        view
     }
 
     pub fn get_view_at(&self, player_id: u64, turn: u32) -> Option<GameHistory> {
         let shared = &self.shared;
-        let BoardHistory {
-            board,
-            state,
-            board_visibility,
-            ..
-        } = &shared.board_history.get(turn as usize)?;
-
         let game_done = matches!(self.state, GameState::Done(_));
 
         if !game_done && self.shared.mods.no_history {
             return None;
         }
 
+        let BoardHistory {
+            board,
+            state,
+            board_visibility,
+            ..
+        } = shared.board_history.get(turn as usize)?;
+
         let (board, board_visibility, _hidden_stones_left) =
-            self.get_board_view(player_id, state, board, board_visibility, game_done);
+            self.get_board_view(player_id, &state, &board, &board_visibility, game_done);
+
+        let last_stone = match state {
+            GameState::Play(state) => state.last_stone.clone(),
+            _ => None,
+        };
 
         Some(GameHistory {
+            last_stone: visible_stones(last_stone, &board, shared.board.width),
             board: board.iter().map(|x| x.0).collect(),
             board_visibility: board_visibility.map(|b| b.iter().map(|x| x.into_value()).collect()),
-            last_stone: state.assume::<PlayState>().last_stone.clone(),
             move_number: turn,
         })
     }
 }
 
+/// Finds the groups from scratch. The rules use [`GroupTracker`] instead, these are kept to
+/// check it against.
 pub fn find_groups(board: &Board) -> Vec<Group> {
+    find_groups_by(board, |a, b| a == b)
+}
+
+/// Like `find_groups`, but stones of different colors form one group if `friendly` says so.
+/// The team of such a group is the color of one of its stones.
+pub fn find_groups_by(board: &Board, friendly: impl Fn(Color, Color) -> bool) -> Vec<Group> {
     let mut legal_points = board
         .points
         .iter()
@@ -884,11 +1604,11 @@ pub fn find_groups(board: &Board) -> Vec<Group> {
                 }
 
                 match board.get_point(point) {
-                    x if x == group.team => {
+                    Color(0) => group.liberties += 1,
+                    x if friendly(x, group.team) => {
                         stack.push_back(point);
                         legal_points.retain(|x| *x != point);
                     }
-                    Color(0) => group.liberties += 1,
                     _ => {}
                 }
             }
@@ -1,20 +1,40 @@
 use serde::{Deserialize, Serialize};
-
-use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
 use super::Color;
 
-#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Board<T = Color> {
     pub width: u32,
     pub height: u32,
     pub toroidal: bool,
     pub points: Vec<T>,
+    /// Zobrist hash of the stones. Only kept up to date on boards of colors, by `set_point`.
+    #[serde(default)]
+    hash: u64,
 }
 
 pub type Point = (u32, u32);
 
+/// Boards are the same if their points are, whether the kept hash is up to date or not.
+impl<T: PartialEq> PartialEq for Board<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.toroidal == other.toroidal
+            && self.points == other.points
+    }
+}
+
+impl<T: Hash> Hash for Board<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.height.hash(state);
+        self.toroidal.hash(state);
+        self.points.hash(state);
+    }
+}
+
 impl<T: Copy + Default> Board<T> {
     pub fn empty(width: u32, height: u32, toroidal: bool) -> Self {
         Board {
@@ -22,6 +42,7 @@ impl<T: Copy + Default> Board<T> {
             height,
             toroidal,
             points: vec![T::default(); (width * height) as usize],
+            hash: 0,
         }
     }
 
@@ -29,14 +50,31 @@ impl<T: Copy + Default> Board<T> {
         (0..self.width).contains(&x) && (0..self.height).contains(&y)
     }
 
+    /// Whether the points fill the board exactly, for boards read from storage.
+    pub(crate) fn fits_points(&self) -> bool {
+        self.width > 0 && self.width as u64 * self.height as u64 == self.points.len() as u64
+    }
+
     pub fn get_point(&self, (x, y): Point) -> T {
         self.points[(y * self.width + x) as usize]
     }
 
-    pub fn point_mut(&mut self, (x, y): Point) -> &mut T {
+    /// Doesn't update the hash, use `set_point` to place stones.
+    pub(crate) fn point_mut(&mut self, (x, y): Point) -> &mut T {
         &mut self.points[(y * self.width + x) as usize]
     }
 
+    /// The board with every point converted. The hash is not kept.
+    pub fn map<U>(&self, f: impl Fn(T) -> U) -> Board<U> {
+        Board {
+            width: self.width,
+            height: self.height,
+            toroidal: self.toroidal,
+            points: self.points.iter().map(|&p| f(p)).collect(),
+            hash: 0,
+        }
+    }
+
     pub fn idx_to_coord(&self, idx: usize) -> Option<Point> {
         if idx < self.points.len() {
             Some((idx as u32 % self.width, idx as u32 / self.width))
@@ -72,14 +110,45 @@ impl<T: Copy + Default> Board<T> {
     }
 }
 
-impl<T: Hash> Board<T> {
+impl Board<Color> {
+    /// Places or removes a stone, updating the hash.
+    pub fn set_point(&mut self, point: Point, color: Color) {
+        let idx = (point.1 * self.width + point.0) as usize;
+        let old = std::mem::replace(&mut self.points[idx], color);
+        self.hash ^= zobrist_key(idx, old) ^ zobrist_key(idx, color);
+    }
+
+    /// Hash of the stones on the board. The same on every platform and version, so it can
+    /// be stored to identify positions.
     pub fn hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        Hash::hash(&self, &mut hasher);
-        hasher.finish()
+        self.hash
+    }
+
+    /// The hash computed over the whole board, for checking the kept one.
+    pub fn full_hash(&self) -> u64 {
+        self.points
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (idx, &color)| hash ^ zobrist_key(idx, color))
     }
 }
 
+/// Random looking key for a stone of the color on the point. Empty points have no key, so an
+/// empty board hashes to zero.
+fn zobrist_key(idx: usize, color: Color) -> u64 {
+    if color.is_empty() {
+        return 0;
+    }
+    splitmix64((idx as u64) << 8 | color.0 as u64)
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 fn wrap_point(x: i32, y: i32, width: i32, height: i32, toroidal: bool) -> Option<Point> {
     if x >= 0 && x < width && y >= 0 && y < height {
         Some((x as u32, y as u32))
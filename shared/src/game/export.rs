@@ -1,5 +1,17 @@
-use super::Board;
+//! SGF export of finished and ongoing games.
+//!
+//! SGF has no property for variants, so the game modifiers are written as JSON in the private
+//! `VG` property for programs to read back, and described in the game comment for people.
+
+use super::fairness;
+use super::BoardHistory;
+use super::Color;
 use super::Game;
+use super::GameModifier;
+use super::GameState;
+use super::ReplayActionKind;
+use super::{Board, Point, VisibilityBoard};
+use std::collections::HashMap;
 use std::fmt::Write;
 
 struct SGFWriter {
@@ -9,7 +21,7 @@ struct SGFWriter {
 impl SGFWriter {
     fn new() -> SGFWriter {
         SGFWriter {
-            buffer: "(;FF[4]GM[1]".to_string(),
+            buffer: "(;FF[4]GM[1]CA[UTF-8]AP[variant-go-server]RU[Chinese]".to_string(),
         }
     }
 
@@ -21,6 +33,11 @@ impl SGFWriter {
         }
     }
 
+    fn property(&mut self, name: &str, value: &str) {
+        let value = value.replace('\\', "\\\\").replace(']', "\\]");
+        let _ = write!(&mut self.buffer, "{}[{}]", name, value);
+    }
+
     fn set_point(&mut self, point: (u32, u32), color: u8) {
         let name = match color {
             0 => "AE",
@@ -34,6 +51,20 @@ impl SGFWriter {
         let _ = write!(&mut self.buffer, "{}[{}{}]", name, x, y);
     }
 
+    /// A move, or a pass if there is no point.
+    fn play(&mut self, point: Option<(u32, u32)>, color: Color) {
+        let name = if color == Color(1) { "B" } else { "W" };
+        match point {
+            Some(point) => {
+                let (x, y) = self.point(point);
+                let _ = write!(&mut self.buffer, "{}[{}{}]", name, x, y);
+            }
+            None => {
+                let _ = write!(&mut self.buffer, "{}[]", name);
+            }
+        }
+    }
+
     fn point(&self, point: (u32, u32)) -> (char, char) {
         let mut letters = 'a'..='z';
         let x = letters.clone().nth(point.0 as usize).unwrap_or('a');
@@ -47,7 +78,19 @@ impl SGFWriter {
         let _ = write!(&mut self.buffer, "LB[{}{}:{}]", x, y, text);
     }
 
-    fn end_turn(&mut self) {
+    /// A property listing points, like markup.
+    fn points(&mut self, name: &str, points: &[(u32, u32)]) {
+        if points.is_empty() {
+            return;
+        }
+        let _ = write!(&mut self.buffer, "{}", name);
+        for &point in points {
+            let (x, y) = self.point(point);
+            let _ = write!(&mut self.buffer, "[{}{}]", x, y);
+        }
+    }
+
+    fn node(&mut self) {
         let _ = write!(&mut self.buffer, ";");
     }
 
@@ -57,40 +100,328 @@ impl SGFWriter {
     }
 }
 
-/// Write a simple single-variation representation of the game.
-/// Limited to two colors so has to use markers for the other colors and hidden stones.
-pub fn sgf_export(game: &Game) -> String {
+/// Write a single-variation record of the game.
+///
+/// Two color games are written as moves where possible. Variants that place more than one
+/// stone, use other colors or hide stones from the rules of go fall back to positions, with
+/// markers for the other colors.
+pub fn sgf_export(game: &Game, nicks: &HashMap<u64, String>) -> String {
     let mut writer = SGFWriter::new();
     let (width, height) = (game.shared.board.width, game.shared.board.height);
     writer.size((width, height));
 
+    game_info(&mut writer, game, nicks);
+
+    let as_moves = expressible_as_moves(game);
     let mut last = Board::empty(width, height, game.shared.board.toroidal);
+    let mut previous: Option<BoardHistory> = None;
 
     for history in &game.shared.board_history {
         let board = &history.board;
 
-        for (idx, (old, new)) in last.points.iter_mut().zip(&board.points).enumerate() {
-            if *old != *new {
-                // Map colored stones to black and white.
-                let color = if new.0 == 0 { 0 } else { (new.0 - 1) % 2 + 1 };
-                writer.set_point(board.idx_to_coord(idx).unwrap(), color);
-                *old = *new;
-            }
+        let played = match &previous {
+            Some(previous) if as_moves => find_move(game, previous, &history),
+            _ => None,
+        };
+
+        if previous.is_some() {
+            writer.node();
         }
 
-        for (idx, new) in board.points.iter().enumerate() {
-            let coord = board.idx_to_coord(idx).unwrap();
-            match new.0 {
-                3 => writer.label(coord, "U"),
-                4 => writer.label(coord, "R"),
-                _ => {}
+        if let Some((point, color)) = played {
+            writer.play(point, color);
+            last = board.clone();
+        } else {
+            for (idx, (old, new)) in last.points.iter_mut().zip(&board.points).enumerate() {
+                if *old != *new {
+                    // Map colored stones to black and white.
+                    let color = if new.0 == 0 { 0 } else { (new.0 - 1) % 2 + 1 };
+                    writer.set_point(board.idx_to_coord(idx).unwrap(), color);
+                    *old = *new;
+                }
+            }
+
+            for (idx, new) in board.points.iter().enumerate() {
+                let coord = board.idx_to_coord(idx).unwrap();
+                match new.0 {
+                    3 => writer.label(coord, "U"),
+                    4 => writer.label(coord, "R"),
+                    5 => writer.label(coord, "N"),
+                    _ => {}
+                }
             }
         }
 
-        writer.end_turn();
+        if let Some(visibility) = &history.board_visibility {
+            let before = previous.as_ref().and_then(|p| p.board_visibility.as_ref());
+            hidden_markers(&mut writer, board, visibility, before);
+        }
 
-        // TODO: PUZZLE markers for hidden stones
+        previous = Some(history);
     }
 
     writer.finish()
 }
+
+/// Marks the stones that are still hidden with `MA` and the ones revealed on this move with
+/// `TR`. The comment tells who could see the hidden stones.
+fn hidden_markers(
+    writer: &mut SGFWriter,
+    board: &Board,
+    visibility: &VisibilityBoard,
+    before: Option<&VisibilityBoard>,
+) {
+    let mut hidden = Vec::new();
+    let mut revealed = Vec::new();
+    let mut comment = String::new();
+
+    for (idx, seen_by) in visibility.points.iter().enumerate() {
+        let coord = board.idx_to_coord(idx).unwrap();
+        let (x, y) = writer.point(coord);
+
+        if !seen_by.is_empty() {
+            let teams = (0..16)
+                .filter(|&team| seen_by.get(team))
+                .map(|team| Color::name(Color(team as u8)))
+                .collect::<Vec<_>>();
+            let _ = writeln!(
+                &mut comment,
+                "{}{} is hidden, seen by {}",
+                x,
+                y,
+                teams.join(", ")
+            );
+            hidden.push(coord);
+        } else if !board.points[idx].is_empty() && before.is_some_and(|b| !b.points[idx].is_empty())
+        {
+            let _ = writeln!(&mut comment, "{}{} was revealed", x, y);
+            revealed.push(coord);
+        }
+    }
+
+    writer.points("MA", &hidden);
+    writer.points("TR", &revealed);
+    if !comment.is_empty() {
+        writer.property("C", comment.trim_end());
+    }
+}
+
+fn game_info(writer: &mut SGFWriter, game: &Game, nicks: &HashMap<u64, String>) {
+    let shared = &game.shared;
+    let two_colors = shared.komis.len() == 2;
+
+    let players = |team: Color| {
+        let mut names = Vec::new();
+        for seat in shared.seats.iter().filter(|s| s.team == team) {
+            let name = seat
+                .player
+                .and_then(|id| nicks.get(&id).cloned())
+                .unwrap_or_else(|| "Unknown".to_string());
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names.join(", ")
+    };
+
+    let mut comment = String::new();
+
+    if two_colors {
+        writer.property("PB", &players(Color(1)));
+        writer.property("PW", &players(Color(2)));
+        let komi = (shared.komis[1] - shared.komis[0]) as f32 / 2.0;
+        writer.property("KM", &komi.to_string());
+    } else {
+        for team in 1..=shared.komis.len() as u8 {
+            let _ = writeln!(&mut comment, "Color {}: {}", team, players(Color(team)));
+        }
+    }
+
+    if let Some(started) = game.started {
+        writer.property("DT", &date(started));
+    }
+
+    let modifiers = modifier_list(&shared.mods);
+    if !modifiers.is_empty() {
+        let _ = writeln!(&mut comment, "Variants: {}", modifiers.join("; "));
+        // The position itself is written as setup stones
+        let mods = GameModifier {
+            start_position: None,
+            ..shared.mods.clone()
+        };
+        let json = serde_json::to_string(&mods).expect("Modifier serialization failed");
+        writer.property("VG", &json);
+    }
+
+    if let GameState::Done(scoring) = &game.state {
+        let team_out = |team: Color| {
+            shared
+                .seats
+                .iter()
+                .filter(|s| s.team == team)
+                .all(|s| s.resigned)
+        };
+
+        // Clocks turn running out of time into resigning
+        let timed_out = game
+            .actions
+            .iter()
+            .rev()
+            .find(|a| {
+                !matches!(
+                    a.action,
+                    ReplayActionKind::TakeSeat(_) | ReplayActionKind::LeaveSeat(_)
+                )
+            })
+            .is_some_and(|a| a.action == ReplayActionKind::Timeout);
+        let reason = if timed_out { "T" } else { "R" };
+
+        let result = if scoring.draw {
+            Some("0".to_string())
+        } else if !two_colors {
+            None
+        } else if team_out(Color(1)) {
+            Some(format!("W+{}", reason))
+        } else if team_out(Color(2)) {
+            Some(format!("B+{}", reason))
+        } else {
+            let diff = (scoring.scores[0] - scoring.scores[1]) as f32 / 2.0;
+            Some(match diff {
+                d if d > 0.0 => format!("B+{}", d),
+                d if d < 0.0 => format!("W+{}", -d),
+                _ => "0".to_string(),
+            })
+        };
+        if let Some(result) = result {
+            writer.property("RE", &result);
+        }
+
+        // The seed is only revealed once the game is over
+        let _ = write!(
+            &mut comment,
+            "Seed: {}\nSeed SHA-256: {}",
+            game.seed,
+            fairness::seed_commitment(game.seed)
+        );
+    }
+
+    if !comment.is_empty() {
+        writer.property("GC", comment.trim_end());
+    }
+}
+
+/// Whether an SGF reader playing the moves by normal go rules ends up with the same boards.
+fn expressible_as_moves(game: &Game) -> bool {
+    let mods = &game.shared.mods;
+    game.shared.komis.len() == 2
+        && mods.shape_rule().is_none()
+        && mods.hidden_move.is_none()
+        && mods.traitor.is_none()
+        && mods.zen_go.is_none()
+        && mods.neutral_stones.is_none()
+        && mods.toroidal.is_none()
+        && mods.alliances.is_none()
+}
+
+/// The single stone or pass that leads from one position to the next.
+fn find_move(
+    game: &Game,
+    previous: &BoardHistory,
+    history: &BoardHistory,
+) -> Option<(Option<Point>, Color)> {
+    let team = game.shared.seats.get(previous.turn)?.team;
+    let (old, new) = (&previous.board, &history.board);
+
+    let mut placed = None;
+    for (idx, (a, b)) in old.points.iter().zip(&new.points).enumerate() {
+        if a == b {
+            continue;
+        }
+        // Anything other than one stone of the moving team and captures needs a position
+        if !a.is_empty() && b.is_empty() {
+            continue;
+        }
+        if a.is_empty() && *b == team && placed.is_none() {
+            placed = new.idx_to_coord(idx);
+            continue;
+        }
+        return None;
+    }
+
+    if placed.is_none() && old.points != new.points {
+        return None;
+    }
+
+    Some((placed, team))
+}
+
+fn modifier_list(mods: &GameModifier) -> Vec<String> {
+    let mut list = Vec::new();
+
+    macro_rules! rule {
+        ($($name:ident),*) => {
+            $(
+                if let Some(rule) = &mods.$name {
+                    list.push(format!("{}: {:?}", stringify!($name), rule));
+                }
+            )*
+        };
+    }
+    macro_rules! flag {
+        ($($name:ident),*) => {
+            $(
+                if mods.$name {
+                    list.push(stringify!($name).to_string());
+                }
+            )*
+        };
+    }
+
+    flag!(pixel, observable, no_history, no_undo);
+    rule!(
+        zen_go,
+        hidden_move,
+        visibility_mode,
+        n_plus_one,
+        captures_give_points,
+        tetris,
+        toroidal,
+        phantom,
+        clock,
+        traitor,
+        ponnuki_is_points,
+        neutral_stones,
+        random_start,
+        last_survivor,
+        alliances,
+        king,
+        shape,
+        move_limit,
+        team_resign
+    );
+
+    // The position itself is written as setup stones
+    if mods.start_position.is_some() {
+        list.push("start_position".to_string());
+    }
+
+    list
+}
+
+/// UTC date of the Unix time as YYYY-MM-DD.
+fn date(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,22 @@
+//! Commit-reveal proof for the random seed.
+//!
+//! Everything random in a game (traitor stones, seeded starting stones) is drawn from
+//! `Game::seed`. The server publishes a SHA-256 hash of the seed when the game starts and
+//! reveals the seed once the game is over. Anyone can then hash the seed themselves and
+//! replay the game to check the random draws.
+
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// Hex encoded SHA-256 of the seed as 8 little-endian bytes.
+pub fn seed_commitment(seed: u64) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(&seed.to_le_bytes()).iter() {
+        let _ = write!(&mut hex, "{:02x}", byte);
+    }
+    hex
+}
+
+pub fn verify_seed(seed: u64, commitment: &str) -> bool {
+    seed_commitment(seed).eq_ignore_ascii_case(commitment)
+}
@@ -0,0 +1,267 @@
+//! Groups of stones kept up to date between moves.
+//!
+//! Finding the groups from scratch walks the whole board, and capture checks need them
+//! several times a move. [`GroupTracker`] keeps the chains of same colored stones in a
+//! union-find instead, and only rebuilds the chains the last changes touched.
+//!
+//! Every chain keeps a count of pseudo liberties: each stone counts its empty neighbors, so a
+//! liberty shared by two stones counts twice. The count is zero exactly when the chain has no
+//! liberties, which is all the capture check needs. Real liberties are counted when a
+//! [`Group`] is built.
+//!
+//! The tracker follows the board by comparing it to the stones it saw last, so it stays
+//! right whatever changed the board, like undoing a move or a traitor stone changing color.
+
+use super::{Alliances, Board, Color, Group, Point};
+
+#[derive(Debug, Clone, Default)]
+pub struct GroupTracker {
+    width: u32,
+    toroidal: bool,
+    /// The stones the chains were built from.
+    stones: Vec<Color>,
+    parent: Vec<usize>,
+    /// Stones of a chain in a cycle, each points to the next one.
+    next: Vec<usize>,
+    /// Number of stones of each chain root.
+    size: Vec<usize>,
+    pseudo_liberties: Vec<usize>,
+    /// Marks for walking the board without clearing anything, see `next_mark`.
+    marks: Vec<u32>,
+    mark: u32,
+}
+
+impl GroupTracker {
+    /// Brings the chains up to date with the board.
+    pub fn update(&mut self, board: &Board) {
+        if self.stones == board.points {
+            return;
+        }
+        if self.stones.len() != board.points.len()
+            || self.width != board.width
+            || self.toroidal != board.toroidal
+        {
+            self.reset(board);
+        }
+
+        let changed = (0..board.points.len())
+            .filter(|&idx| self.stones[idx] != board.points[idx])
+            .collect::<Vec<_>>();
+
+        // Chains that lost or changed a stone may split, so they are built again.
+        let mark = self.next_mark();
+        let mut fresh = Vec::new();
+        for &idx in &changed {
+            if self.stones[idx].is_empty() || self.marks[idx] == mark {
+                continue;
+            }
+            let mut stone = idx;
+            loop {
+                self.marks[stone] = mark;
+                fresh.push(stone);
+                stone = self.next[stone];
+                if stone == idx {
+                    break;
+                }
+            }
+        }
+        for &idx in &changed {
+            if self.marks[idx] != mark {
+                self.marks[idx] = mark;
+                fresh.push(idx);
+            }
+        }
+
+        // Chains that are kept only see their neighbors fill up or empty.
+        for &idx in &changed {
+            let was_empty = self.stones[idx].is_empty();
+            let is_empty = board.points[idx].is_empty();
+            for neighbor in neighbors(board, idx) {
+                if board.points[neighbor].is_empty() || self.marks[neighbor] == mark {
+                    continue;
+                }
+                let root = self.root(neighbor);
+                match (was_empty, is_empty) {
+                    (true, false) => self.pseudo_liberties[root] -= 1,
+                    (false, true) => self.pseudo_liberties[root] += 1,
+                    _ => {}
+                }
+            }
+            self.stones[idx] = board.points[idx];
+        }
+
+        fresh.retain(|&idx| !board.points[idx].is_empty());
+        for &idx in &fresh {
+            self.parent[idx] = idx;
+            self.next[idx] = idx;
+            self.size[idx] = 1;
+            self.pseudo_liberties[idx] = neighbors(board, idx)
+                .filter(|&n| board.points[n].is_empty())
+                .count();
+        }
+        for &idx in &fresh {
+            for neighbor in neighbors(board, idx) {
+                if board.points[neighbor] == board.points[idx] {
+                    self.union(idx, neighbor);
+                }
+            }
+        }
+    }
+
+    /// Every group on the board. Stones of allied teams form one group, like in play.
+    pub fn groups(&mut self, board: &Board, alliances: Option<&Alliances>) -> Vec<Group> {
+        self.update(board);
+        self.collect(board, alliances, false)
+    }
+
+    /// The groups without liberties.
+    pub fn dead_groups(&mut self, board: &Board, alliances: Option<&Alliances>) -> Vec<Group> {
+        self.update(board);
+        self.collect(board, alliances, true)
+    }
+
+    /// The group the stone on the point belongs to.
+    pub fn group_at(
+        &mut self,
+        board: &Board,
+        point: Point,
+        alliances: Option<&Alliances>,
+    ) -> Option<Group> {
+        self.update(board);
+        let idx = (point.1 * board.width + point.0) as usize;
+        if board.points[idx].is_empty() {
+            return None;
+        }
+        let seen = self.next_mark();
+        Some(self.build_group(board, self.root(idx), alliances, seen))
+    }
+
+    fn collect(&mut self, board: &Board, alliances: Option<&Alliances>, dead: bool) -> Vec<Group> {
+        // A group can only be dead if one of its chains has no liberties
+        let roots = (0..board.points.len())
+            .filter(|&idx| !board.points[idx].is_empty() && self.parent[idx] == idx)
+            .filter(|&idx| !dead || self.pseudo_liberties[idx] == 0)
+            .collect::<Vec<_>>();
+
+        // Chains already in a group are marked with the mark of the whole search
+        let seen = self.next_mark();
+        let mut groups = Vec::new();
+        for root in roots {
+            if self.marks[root] == seen {
+                continue;
+            }
+            let group = self.build_group(board, root, alliances, seen);
+            if !dead || group.liberties == 0 {
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// Walks the chains allied to the one at `root`. They are marked with `seen`, the
+    /// liberties with a mark of their own.
+    fn build_group(
+        &mut self,
+        board: &Board,
+        root: usize,
+        alliances: Option<&Alliances>,
+        seen: u32,
+    ) -> Group {
+        let team = board.points[root];
+        let liberty_mark = self.next_mark();
+
+        let mut group = Group {
+            team,
+            alive: true,
+            ..Group::default()
+        };
+        let mut chains = vec![root];
+        self.marks[root] = seen;
+        while let Some(chain) = chains.pop() {
+            let mut stone = chain;
+            loop {
+                group.points.push(board.idx_to_coord(stone).unwrap());
+                for neighbor in neighbors(board, stone) {
+                    let color = board.points[neighbor];
+                    if color.is_empty() {
+                        if self.marks[neighbor] != liberty_mark {
+                            self.marks[neighbor] = liberty_mark;
+                            group.liberties += 1;
+                        }
+                    } else if color != board.points[stone] && allied(alliances, color, team) {
+                        let other = self.root(neighbor);
+                        if self.marks[other] != seen {
+                            self.marks[other] = seen;
+                            chains.push(other);
+                        }
+                    }
+                }
+                stone = self.next[stone];
+                if stone == chain {
+                    break;
+                }
+            }
+        }
+        group
+    }
+
+    fn reset(&mut self, board: &Board) {
+        let len = board.points.len();
+        self.width = board.width;
+        self.toroidal = board.toroidal;
+        self.stones = vec![Color::empty(); len];
+        self.parent = (0..len).collect();
+        self.next = (0..len).collect();
+        self.size = vec![1; len];
+        self.pseudo_liberties = vec![0; len];
+        self.marks = vec![0; len];
+        self.mark = 0;
+    }
+
+    fn next_mark(&mut self) -> u32 {
+        if self.mark == u32::MAX {
+            self.marks.iter_mut().for_each(|m| *m = 0);
+            self.mark = 0;
+        }
+        self.mark += 1;
+        self.mark
+    }
+
+    fn root(&self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        if a == b {
+            return;
+        }
+        let (big, small) = if self.size[a] >= self.size[b] {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+        self.pseudo_liberties[big] += self.pseudo_liberties[small];
+        // Joining two cycles into one is a swap
+        self.next.swap(big, small);
+    }
+}
+
+fn allied(alliances: Option<&Alliances>, a: Color, b: Color) -> bool {
+    match alliances {
+        Some(alliances) => alliances.allied(a, b),
+        None => a == b,
+    }
+}
+
+fn neighbors(board: &Board, idx: usize) -> impl Iterator<Item = usize> {
+    let width = board.width;
+    board
+        .surrounding_points(board.idx_to_coord(idx).unwrap())
+        .map(move |(x, y)| (y * width + x) as usize)
+}
@@ -0,0 +1,522 @@
+//! Positions of every move of a game.
+//!
+//! Keeping a whole board for every move adds up in long games on big boards, so the boards
+//! are stored as the points that changed since the previous move. Every
+//! [`KEYFRAME_INTERVAL`]th move has the whole board, so getting any position only takes
+//! replaying the changes since the keyframe before it. Kings, eliminated teams and resign
+//! votes rarely change, so they are only stored when they do. The same goes for the game
+//! state apart from the last stone, and the traitor state is stored as the one color drawn in
+//! the move. The last position is kept whole, as it is the one the rules need.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::mem::size_of;
+
+use super::{Board, BoardHistory, Color, GroupVec, Point, Visibility};
+use crate::states::play::traitor::TraitorState;
+use crate::states::{GameState, PlayState};
+
+/// Moves from one whole board to the next.
+pub const KEYFRAME_INTERVAL: usize = 32;
+
+#[derive(Clone, Serialize, Deserialize)]
+enum Frame<T> {
+    Full(Board<T>),
+    /// Points that changed since the previous move, by index.
+    Diff(Vec<(u32, T)>),
+}
+
+impl<T: Copy + Default + PartialEq> Frame<T> {
+    fn new(previous: Option<&Board<T>>, board: &Board<T>, keyframe: bool) -> Self {
+        match previous {
+            Some(previous) if !keyframe && previous.points.len() == board.points.len() => {
+                let changes = previous
+                    .points
+                    .iter()
+                    .zip(&board.points)
+                    .enumerate()
+                    .filter(|(_, (a, b))| a != b)
+                    .map(|(idx, (_, &b))| (idx as u32, b))
+                    .collect();
+                Frame::Diff(changes)
+            }
+            _ => Frame::Full(board.clone()),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            Frame::Full(board) => board.points.len() * size_of::<T>(),
+            Frame::Diff(changes) => changes.len() * size_of::<(u32, T)>(),
+        }
+    }
+}
+
+/// The game state of a move.
+#[derive(Clone, Serialize, Deserialize)]
+enum StateFrame {
+    Full(Box<GameState>),
+    /// The play state of the previous move with a new last stone. Boxed, as a whole
+    /// `GroupVec` would make every move as big as the longest shape.
+    Play {
+        last_stone: Option<Box<[Point]>>,
+        capture_count: usize,
+        /// Only stored when someone passed or the passes were reset.
+        players_passed: Option<Box<[bool]>>,
+    },
+}
+
+impl StateFrame {
+    fn new(previous: Option<&GameState>, state: &GameState, keyframe: bool) -> Self {
+        if let (Some(GameState::Play(before)), GameState::Play(after)) = (previous, state) {
+            let same = PlayState {
+                players_passed: after.players_passed.clone(),
+                last_stone: after.last_stone.clone(),
+                capture_count: after.capture_count,
+                ..before.clone()
+            };
+            if !keyframe && &same == after {
+                let players_passed = if before.players_passed == after.players_passed {
+                    None
+                } else {
+                    Some(after.players_passed.as_slice().into())
+                };
+                return StateFrame::Play {
+                    last_stone: after.last_stone.as_ref().map(|s| s.as_slice().into()),
+                    capture_count: after.capture_count,
+                    players_passed,
+                };
+            }
+        }
+        StateFrame::Full(Box::new(state.clone()))
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            StateFrame::Full(state) => {
+                let vecs = match &**state {
+                    GameState::Play(play) => play.players_passed.len() + play.draw_proposed.len(),
+                    _ => 0,
+                };
+                size_of::<GameState>() + vecs
+            }
+            StateFrame::Play {
+                last_stone,
+                players_passed,
+                ..
+            } => {
+                last_stone
+                    .as_ref()
+                    .map_or(0, |s| s.len() * size_of::<Point>())
+                    + players_passed.as_ref().map_or(0, |p| p.len())
+            }
+        }
+    }
+}
+
+/// How the traitor state got from the previous move to this one.
+#[derive(Clone, Serialize, Deserialize)]
+enum TraitorFrame {
+    Full(Option<Box<TraitorState>>),
+    /// One stone color was drawn for the team.
+    Drawn(Color),
+    Same,
+}
+
+impl TraitorFrame {
+    fn new(
+        previous: Option<&Option<TraitorState>>,
+        traitor: &Option<TraitorState>,
+        keyframe: bool,
+    ) -> Self {
+        match (previous, traitor) {
+            (Some(Some(before)), Some(after)) if !keyframe => match after.draws_since(before) {
+                (0, _) => TraitorFrame::Same,
+                (1, team) => TraitorFrame::Drawn(team),
+                _ => TraitorFrame::Full(Some(Box::new(after.clone()))),
+            },
+            (Some(None), None) if !keyframe => TraitorFrame::Same,
+            _ => TraitorFrame::Full(traitor.clone().map(Box::new)),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            TraitorFrame::Full(Some(_)) => size_of::<TraitorState>(),
+            _ => 0,
+        }
+    }
+}
+
+/// The parts of a move that only some variants change.
+#[derive(Clone, Serialize, Deserialize)]
+struct Rare {
+    eliminated: GroupVec<Color>,
+    kings: GroupVec<Option<Point>>,
+    #[serde(default)]
+    resign_votes: GroupVec<bool>,
+}
+
+impl Rare {
+    fn new(history: &BoardHistory) -> Self {
+        Rare {
+            eliminated: history.eliminated.clone(),
+            kings: history.kings.clone(),
+            resign_votes: history.resign_votes.clone(),
+        }
+    }
+
+    fn same(&self, other: &Rare) -> bool {
+        self.eliminated == other.eliminated
+            && self.kings == other.kings
+            && self.resign_votes == other.resign_votes
+    }
+}
+
+/// A move without its boards.
+#[derive(Clone, Serialize, Deserialize)]
+struct Entry {
+    hash: u64,
+    board: Frame<Color>,
+    /// Visibility as the values of the bitmaps.
+    board_visibility: Option<Frame<u16>>,
+    state: StateFrame,
+    points: GroupVec<i32>,
+    turn: usize,
+    traitor: TraitorFrame,
+    /// `None` if these didn't change since the previous move.
+    rare: Option<Box<Rare>>,
+}
+
+impl Entry {
+    fn new(previous: Option<&BoardHistory>, history: &BoardHistory, keyframe: bool) -> Self {
+        let board = Frame::new(previous.map(|p| &p.board), &history.board, keyframe);
+        let board_visibility = history.board_visibility.as_ref().map(|visibility| {
+            let before = previous
+                .and_then(|p| p.board_visibility.as_ref())
+                .map(|b| b.map(Visibility::into_value));
+            let visibility = visibility.map(Visibility::into_value);
+            Frame::new(before.as_ref(), &visibility, keyframe)
+        });
+
+        let rare = Rare::new(history);
+        let rare = match previous {
+            Some(previous) if !keyframe && Rare::new(previous).same(&rare) => None,
+            _ => Some(Box::new(rare)),
+        };
+
+        Entry {
+            hash: history.hash,
+            board,
+            board_visibility,
+            state: StateFrame::new(previous.map(|p| &p.state), &history.state, keyframe),
+            points: history.points.clone(),
+            turn: history.turn,
+            traitor: TraitorFrame::new(previous.map(|p| &p.traitor), &history.traitor, keyframe),
+            rare,
+        }
+    }
+
+    /// The move, given the one before it. Keyframes don't need the previous move. `None` if
+    /// the changes don't fit the previous move.
+    fn restore(&self, previous: Option<BoardHistory>) -> Option<BoardHistory> {
+        let (board, board_visibility, rare, state, traitor) = match previous {
+            Some(p) => {
+                let rare = Rare::new(&p);
+                (
+                    Some(p.board),
+                    p.board_visibility,
+                    Some(rare),
+                    Some(p.state),
+                    Some(p.traitor),
+                )
+            }
+            None => (None, None, None, None, None),
+        };
+        let rare = match &self.rare {
+            Some(rare) => (**rare).clone(),
+            None => rare?,
+        };
+
+        let board = match &self.board {
+            Frame::Full(board) if board.fits_points() => board.clone(),
+            Frame::Full(_) => return None,
+            Frame::Diff(changes) => {
+                let mut board = board?;
+                for &(idx, color) in changes {
+                    let point = board.idx_to_coord(idx as usize)?;
+                    board.set_point(point, color);
+                }
+                board
+            }
+        };
+
+        let board_visibility = match &self.board_visibility {
+            None => None,
+            Some(Frame::Full(visibility)) => Some(visibility.map(Visibility::from_value)),
+            Some(Frame::Diff(changes)) => {
+                let mut visibility = board_visibility?;
+                for &(idx, seen) in changes {
+                    *visibility.points.get_mut(idx as usize)? = Visibility::from_value(seen);
+                }
+                Some(visibility)
+            }
+        };
+        if let Some(visibility) = &board_visibility {
+            if (visibility.width, visibility.height) != (board.width, board.height)
+                || visibility.points.len() != board.points.len()
+            {
+                return None;
+            }
+        }
+
+        let state = match &self.state {
+            StateFrame::Full(state) => (**state).clone(),
+            StateFrame::Play {
+                last_stone,
+                capture_count,
+                players_passed,
+            } => {
+                let mut play = match state? {
+                    GameState::Play(play) => play,
+                    _ => return None,
+                };
+                play.last_stone = last_stone.as_deref().map(GroupVec::from);
+                play.capture_count = *capture_count;
+                if let Some(passed) = players_passed {
+                    play.players_passed = passed.to_vec();
+                }
+                GameState::Play(play)
+            }
+        };
+
+        let traitor = match &self.traitor {
+            TraitorFrame::Full(traitor) => traitor.as_deref().cloned(),
+            TraitorFrame::Drawn(team) => {
+                let mut traitor = traitor.flatten()?;
+                traitor.next_color(*team);
+                Some(traitor)
+            }
+            TraitorFrame::Same => traitor?,
+        };
+
+        Some(BoardHistory {
+            hash: self.hash,
+            board,
+            board_visibility,
+            state,
+            points: self.points.clone(),
+            turn: self.turn,
+            traitor,
+            eliminated: rare.eliminated,
+            kings: rare.kings,
+            resign_votes: rare.resign_votes,
+        })
+    }
+
+    fn is_keyframe(&self) -> bool {
+        matches!(self.board, Frame::Full(_))
+            && !matches!(self.board_visibility, Some(Frame::Diff(_)))
+            && matches!(self.state, StateFrame::Full(_))
+            && matches!(self.traitor, TraitorFrame::Full(_))
+            && self.rare.is_some()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    /// The last move as a whole.
+    last: Option<BoardHistory>,
+}
+
+impl History {
+    pub fn new(first: BoardHistory) -> Self {
+        let mut history = History::default();
+        history.push(first);
+        history
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn push(&mut self, history: BoardHistory) {
+        let since_keyframe = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| !entry.is_keyframe())
+            .count();
+        let keyframe = since_keyframe + 1 >= KEYFRAME_INTERVAL;
+        self.entries
+            .push(Entry::new(self.last.as_ref(), &history, keyframe));
+        self.last = Some(history);
+    }
+
+    pub fn pop(&mut self) -> Option<BoardHistory> {
+        self.entries.pop()?;
+        let last = self
+            .entries
+            .len()
+            .checked_sub(1)
+            .map(|idx| self.restore(idx));
+        std::mem::replace(&mut self.last, last)
+    }
+
+    pub fn last(&self) -> Option<&BoardHistory> {
+        self.last.as_ref()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<BoardHistory> {
+        if idx + 1 == self.len() {
+            return self.last.clone();
+        }
+        if idx >= self.len() {
+            return None;
+        }
+        Some(self.restore(idx))
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            history: self,
+            idx: 0,
+            previous: None,
+        }
+    }
+
+    /// Whether the board is one of the last `depth` positions.
+    pub fn repeats(&self, board: &Board, depth: usize) -> bool {
+        let start = self.len().saturating_sub(depth);
+        (start..self.len())
+            .rev()
+            .filter(|&idx| self.entries[idx].hash == board.hash())
+            .any(|idx| self.get(idx).is_some_and(|h| &h.board == board))
+    }
+
+    /// Rough number of bytes the moves take, not counting spare capacity.
+    pub fn memory_size(&self) -> usize {
+        let entries = self
+            .entries
+            .iter()
+            .map(|entry| {
+                size_of::<Entry>()
+                    + entry.board.heap_size()
+                    + entry.board_visibility.as_ref().map_or(0, Frame::heap_size)
+                    + entry.state.heap_size()
+                    + entry.traitor.heap_size()
+                    + entry.rare.as_ref().map_or(0, |_| size_of::<Rare>())
+            })
+            .sum::<usize>();
+        let last = self.last.as_ref().map_or(0, |last| {
+            size_of::<BoardHistory>()
+                + last.board.points.len() * size_of::<Color>()
+                + last
+                    .board_visibility
+                    .as_ref()
+                    .map_or(0, |v| v.points.len() * size_of::<Visibility>())
+        });
+        entries + last
+    }
+
+    /// The part of `memory_size` that the game and traitor states of the moves take.
+    pub fn state_memory_size(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|entry| {
+                size_of::<StateFrame>()
+                    + size_of::<TraitorFrame>()
+                    + entry.state.heap_size()
+                    + entry.traitor.heap_size()
+            })
+            .sum()
+    }
+
+    fn restore(&self, idx: usize) -> BoardHistory {
+        let keyframe = (0..=idx)
+            .rev()
+            .find(|&i| self.entries[i].is_keyframe())
+            .expect("history has no keyframe");
+
+        let mut history = self.entries[keyframe].restore(None);
+        for entry in &self.entries[keyframe + 1..=idx] {
+            history = entry.restore(history);
+        }
+        history.expect("moves are checked when loaded")
+    }
+}
+
+/// Only the moves are stored, the last one is restored from them.
+impl Serialize for History {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.entries.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for History {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<Entry>::deserialize(deserializer)?;
+        if entries.first().is_some_and(|entry| !entry.is_keyframe()) {
+            return Err(D::Error::custom("history doesn't start with a keyframe"));
+        }
+        let mut since_keyframe = 0;
+        for entry in &entries {
+            since_keyframe = if entry.is_keyframe() {
+                0
+            } else {
+                since_keyframe + 1
+            };
+            if since_keyframe >= KEYFRAME_INTERVAL {
+                return Err(D::Error::custom("history is missing a keyframe"));
+            }
+        }
+
+        // Restoring every move checks that the changes fit, so they can't fail later
+        let mut last = None;
+        for (idx, entry) in entries.iter().enumerate() {
+            last = entry.restore(last);
+            if last.is_none() {
+                return Err(D::Error::custom(format!(
+                    "move {} doesn't fit the history",
+                    idx
+                )));
+            }
+        }
+        Ok(History { entries, last })
+    }
+}
+
+/// Goes through the moves in order, restoring each from the one before.
+pub struct Iter<'a> {
+    history: &'a History,
+    idx: usize,
+    previous: Option<BoardHistory>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = BoardHistory;
+
+    fn next(&mut self) -> Option<BoardHistory> {
+        let entry = self.history.entries.get(self.idx)?;
+        let history = entry
+            .restore(self.previous.take())
+            .expect("moves are checked when loaded");
+        self.idx += 1;
+        self.previous = Some(history.clone());
+        Some(history)
+    }
+}
+
+impl<'a> IntoIterator for &'a History {
+    type Item = BoardHistory;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
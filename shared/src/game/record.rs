@@ -0,0 +1,94 @@
+//! Native game records.
+//!
+//! SGF can't describe games with more than two colors or most of the variants, so games are
+//! also stored in a JSON format of our own. A record holds everything needed to play the game
+//! again from the start:
+//!
+//! ```json
+//! {
+//!   "format": "variant-go",
+//!   "version": 1,
+//!   "size": [9, 9],
+//!   "seats": [1, 2, 3],
+//!   "komis": [0, 0, 0],
+//!   "mods": { "pixel": false, "toroidal": {}, ... },
+//!   "seed": 1234,
+//!   "actions": [
+//!     { "user_id": 5, "action": { "TakeSeat": 0 } },
+//!     { "user_id": 5, "action": { "Play": { "Place": [2, 2] } } },
+//!     { "user_id": 5, "action": { "Play": "Pass" } }
+//!   ]
+//! }
+//! ```
+//!
+//! `seats` has the color of every seat and `komis` the komi of every color in half points.
+//! `mods` is [`GameModifier`] as is, and `actions` every seat change and move in the order
+//! they were made. Clocks are recorded but not run when a record is imported.
+//!
+//! The version is the replay version of the rules, see [`super::REPLAY_VERSION`].
+
+use serde::{Deserialize, Serialize};
+
+use super::{Game, GameAction, GameModifier, LoadError};
+
+pub const FORMAT: &str = "variant-go";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub format: String,
+    /// Same as the replay version of the game.
+    pub version: u32,
+    pub size: (u8, u8),
+    pub seats: Vec<u8>,
+    pub komis: Vec<i32>,
+    pub mods: GameModifier,
+    pub seed: u64,
+    pub actions: Vec<GameAction>,
+}
+
+impl Game {
+    pub fn record(&self) -> GameRecord {
+        let shared = &self.shared;
+        GameRecord {
+            format: FORMAT.to_string(),
+            version: shared.version,
+            size: (shared.board.width as _, shared.board.height as _),
+            seats: shared.seats.iter().map(|x| x.team.0).collect(),
+            komis: shared.komis.iter().copied().collect(),
+            mods: shared.mods.clone(),
+            seed: self.seed,
+            actions: self.actions.clone(),
+        }
+    }
+
+    /// The game as a pretty printed JSON record.
+    pub fn export_record(&self) -> String {
+        serde_json::to_string_pretty(&self.record()).expect("Record export failed")
+    }
+
+    pub fn import_record(text: &str) -> Result<Game, LoadError> {
+        let mut record: GameRecord =
+            serde_json::from_str(text).map_err(|e| LoadError::Decode(e.to_string()))?;
+        if record.format != FORMAT {
+            return Err(LoadError::Decode(format!(
+                "unknown format {}",
+                record.format
+            )));
+        }
+
+        // Play the moves without a clock but keep the setting in the game
+        let clock = record.mods.clock.take();
+        let mut game = Game::replay(
+            &record.seats,
+            record.komis.into_iter().collect(),
+            record.size,
+            record.mods,
+            record.seed,
+            record.version,
+            record.actions,
+        )?;
+        game.shared.mods.clock = clock;
+
+        Ok(game)
+    }
+}
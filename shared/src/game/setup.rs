@@ -0,0 +1,350 @@
+//! Checks that a game setup makes sense before the game is created.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+
+use super::{
+    find_groups, find_groups_by, Board, Color, GameModifier, OneColorChoice, StartPosition,
+    VisibilityMode,
+};
+
+/// Names of the modifiers, for error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Modifier {
+    Pixel,
+    ZenGo,
+    HiddenMove,
+    Traitor,
+    NeutralStones,
+    RandomStart,
+    LastSurvivor,
+    Alliances,
+    King,
+    Shape,
+    MoveLimit,
+    TeamResign,
+    StartPosition,
+    OneColor,
+}
+
+impl Modifier {
+    pub fn name(self) -> &'static str {
+        match self {
+            Modifier::Pixel => "Pixel go",
+            Modifier::ZenGo => "Zen go",
+            Modifier::HiddenMove => "Hidden move go",
+            Modifier::Traitor => "Traitor go",
+            Modifier::NeutralStones => "Neutral stones",
+            Modifier::RandomStart => "Random start",
+            Modifier::LastSurvivor => "Last survivor",
+            Modifier::Alliances => "Alliances",
+            Modifier::King => "King go",
+            Modifier::Shape => "Shape go",
+            Modifier::MoveLimit => "Move limit",
+            Modifier::TeamResign => "Team resign",
+            Modifier::StartPosition => "Start position",
+            Modifier::OneColor => "One color go",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SetupError {
+    /// Seats must be between 1 and 7
+    SeatCount(usize),
+    /// Teams must be between 1 and 4
+    TeamCount(usize),
+    /// A seat belongs to a team that doesn't exist
+    SeatTeam {
+        seat: usize,
+        team: u8,
+    },
+    BoardSize(u8, u8),
+    /// The two modifiers can't be used together
+    Conflict(Modifier, Modifier),
+    /// The modifier doesn't work with these settings
+    Invalid {
+        modifier: Modifier,
+        reason: Cow<'static, str>,
+    },
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupError::SeatCount(count) => write!(f, "{} seats, must be 1 to 7", count),
+            SetupError::TeamCount(count) => write!(f, "{} colors, must be 1 to 4", count),
+            SetupError::SeatTeam { seat, team } => {
+                write!(
+                    f,
+                    "Seat {} has color {} which is not in the game",
+                    seat + 1,
+                    team
+                )
+            }
+            SetupError::BoardSize(w, h) => write!(f, "Board size {}x{} is not allowed", w, h),
+            SetupError::Conflict(a, b) => {
+                write!(f, "{} can't be used with {}", a.name(), b.name())
+            }
+            SetupError::Invalid { modifier, reason } => {
+                write!(f, "{}: {}", modifier.name(), reason)
+            }
+        }
+    }
+}
+
+/// Lists everything wrong with the setup, or nothing if the game can be created.
+pub fn validate(
+    seats: &[u8],
+    team_count: usize,
+    size: (u8, u8),
+    mods: &GameModifier,
+) -> Result<(), Vec<SetupError>> {
+    use Modifier::*;
+
+    let mut errors = Vec::new();
+    let mut conflict = |enabled: bool, a, b| {
+        if enabled {
+            errors.push(SetupError::Conflict(a, b));
+        }
+    };
+
+    conflict(
+        mods.zen_go.is_some() && mods.hidden_move.is_some(),
+        ZenGo,
+        HiddenMove,
+    );
+    conflict(
+        mods.zen_go.is_some() && mods.last_survivor.is_some(),
+        ZenGo,
+        LastSurvivor,
+    );
+    conflict(
+        mods.zen_go.is_some() && mods.alliances.is_some(),
+        ZenGo,
+        Alliances,
+    );
+    // Kings belong to a team, so the colors can't change
+    conflict(mods.zen_go.is_some() && mods.king.is_some(), ZenGo, King);
+    conflict(mods.pixel && mods.shape.is_some(), Pixel, Shape);
+    // Everyone is on the same team in zen go
+    conflict(
+        mods.zen_go.is_some() && mods.team_resign.is_some(),
+        ZenGo,
+        TeamResign,
+    );
+
+    // 7 = 3 colors, rengo
+    // 4 = 4 colors
+    if !(1..=7).contains(&seats.len()) {
+        errors.push(SetupError::SeatCount(seats.len()));
+    }
+    if !(1..=4).contains(&team_count) {
+        errors.push(SetupError::TeamCount(team_count));
+    }
+
+    for (seat, &team) in seats.iter().enumerate() {
+        if team == 0 || team as usize > team_count {
+            errors.push(SetupError::SeatTeam { seat, team });
+        }
+    }
+
+    // Don't allow huge boards
+    if size.0 == 0 || size.1 == 0 || size.0 > 25 || size.1 > 25 {
+        errors.push(SetupError::BoardSize(size.0, size.1));
+    }
+
+    let mut invalid = |enabled: bool, modifier, reason: &'static str| {
+        if enabled {
+            errors.push(SetupError::Invalid {
+                modifier,
+                reason: Cow::from(reason),
+            });
+        }
+    };
+
+    if let Some(zen) = &mods.zen_go {
+        invalid(
+            zen.color_count != 0 && zen.color_count as usize != team_count,
+            ZenGo,
+            "color count doesn't match the komis",
+        );
+        invalid(team_count < 2, ZenGo, "needs at least two colors");
+    }
+
+    invalid(
+        mods.traitor.is_some() && team_count < 2,
+        Traitor,
+        "needs at least two colors",
+    );
+
+    // Knocking out teams makes no sense with two teams
+    invalid(
+        mods.last_survivor.is_some() && team_count < 3,
+        LastSurvivor,
+        "needs at least three colors",
+    );
+
+    if let Some(alliances) = &mods.alliances {
+        invalid(
+            alliances.teams.len() != team_count,
+            Alliances,
+            "every color needs an alliance",
+        );
+        invalid(
+            alliances.teams.iter().all(|&a| a == alliances.teams[0]),
+            Alliances,
+            "everyone is in the same alliance",
+        );
+    }
+
+    if let Some(rule) = &mods.shape {
+        invalid(rule.shapes.is_empty(), Shape, "no shapes to place");
+        invalid(
+            rule.shapes.iter().any(|s| s.points.is_empty()),
+            Shape,
+            "shapes can't be empty",
+        );
+        invalid(
+            rule.shapes.iter().any(|s| has_duplicates(&s.points)),
+            Shape,
+            "shapes can't cover a point twice",
+        );
+        invalid(
+            rule.shapes.iter().any(|s| !is_connected(&s.points)),
+            Shape,
+            "shapes must be connected",
+        );
+    }
+
+    if let Some(rule) = &mods.move_limit {
+        invalid(rule.moves == 0, MoveLimit, "needs at least one move");
+    }
+
+    if let Some(position) = &mods.start_position {
+        invalid(
+            position.size != size || position.points.len() != size.0 as usize * size.1 as usize,
+            StartPosition,
+            "position doesn't match the board size",
+        );
+        invalid(
+            position.points.iter().any(|&c| c as usize > team_count),
+            StartPosition,
+            "position has colors that are not in the game",
+        );
+        invalid(
+            !seats.contains(&position.to_move),
+            StartPosition,
+            "nobody plays the color to move",
+        );
+        invalid(
+            position.size == size && has_dead_groups(position, mods),
+            StartPosition,
+            "position has stones without liberties",
+        );
+    }
+
+    if let Some(VisibilityMode::OneColor(OneColorChoice::Fixed(color))) = mods.visibility_mode {
+        invalid(
+            color.is_empty() || color.as_usize() > team_count,
+            OneColor,
+            "stones must be shown as one of the colors in the game",
+        );
+    }
+
+    let seeded_stones = mods.neutral_stones.as_ref().map_or(0, |n| n.count)
+        + mods.random_start.as_ref().map_or(0, |r| r.stones_per_team) * team_count as u32;
+    // Leave most of the board for the players
+    if seeded_stones > size.0 as u32 * size.1 as u32 / 4 {
+        let modifier = if mods.neutral_stones.is_some() {
+            NeutralStones
+        } else {
+            RandomStart
+        };
+        invalid(true, modifier, "too many stones for the board");
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// The checks games had before setups were validated. Games of replay version 0 were
+/// created with only these, so they can have any modifiers and seats of any color up to 4.
+pub fn validate_legacy(
+    seats: &[u8],
+    team_count: usize,
+    size: (u8, u8),
+) -> Result<(), Vec<SetupError>> {
+    let mut errors = Vec::new();
+
+    if !(1..=7).contains(&seats.len()) {
+        errors.push(SetupError::SeatCount(seats.len()));
+    }
+    if !(1..=4).contains(&team_count) {
+        errors.push(SetupError::TeamCount(team_count));
+    }
+    for (seat, &team) in seats.iter().enumerate() {
+        if !(1..=4).contains(&team) {
+            errors.push(SetupError::SeatTeam { seat, team });
+        }
+    }
+    if size.0 > 25 || size.1 > 25 {
+        errors.push(SetupError::BoardSize(size.0, size.1));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Whether the position has a group that should already have been captured.
+fn has_dead_groups(position: &StartPosition, mods: &GameModifier) -> bool {
+    let (width, height) = position.size;
+    let mut board = Board::empty(width as _, height as _, mods.toroidal.is_some());
+    for (idx, &color) in position.points.iter().enumerate() {
+        if let Some(point) = board.idx_to_coord(idx) {
+            board.set_point(point, Color(color));
+        }
+    }
+
+    let groups = match &mods.alliances {
+        Some(alliances) => find_groups_by(&board, |a, b| alliances.allied(a, b)),
+        None => find_groups(&board),
+    };
+    groups.iter().any(|group| group.liberties == 0)
+}
+
+fn has_duplicates(points: &[(i8, i8)]) -> bool {
+    points
+        .iter()
+        .enumerate()
+        .any(|(idx, p)| points[..idx].contains(p))
+}
+
+/// Whether every point of the shape can be reached from the first through its neighbors.
+fn is_connected(points: &[(i8, i8)]) -> bool {
+    let first = match points.first() {
+        Some(&p) => p,
+        None => return true,
+    };
+
+    let mut reached = vec![first];
+    let mut stack = vec![first];
+    while let Some((x, y)) = stack.pop() {
+        let neighbors = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        for p in points {
+            if neighbors.contains(p) && !reached.contains(p) {
+                reached.push(*p);
+                stack.push(*p);
+            }
+        }
+    }
+
+    points.iter().all(|p| reached.contains(p))
+}
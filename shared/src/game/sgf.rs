@@ -0,0 +1,335 @@
+//! Reads positions from SGF files so games can start from them.
+//!
+//! Only the main variation is read. Moves are played by the normal rules of go, captures
+//! included, but nothing is checked for legality.
+
+use std::fmt;
+
+use super::{Board, Color, Point, StartPosition};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SgfError {
+    /// Unexpected character at the byte offset
+    Syntax(usize),
+    UnexpectedEnd,
+    /// The file has no game in it
+    Empty,
+    BoardSize(String),
+    /// A point that is not on the board
+    Point(String),
+    /// Variations are nested deeper than [`MAX_DEPTH`]
+    TooDeep,
+}
+
+impl fmt::Display for SgfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SgfError::Syntax(offset) => write!(f, "Unexpected character at {}", offset),
+            SgfError::UnexpectedEnd => write!(f, "The file ends in the middle of the game"),
+            SgfError::Empty => write!(f, "There is no game in the file"),
+            SgfError::BoardSize(size) => write!(f, "Board size {} is not supported", size),
+            SgfError::Point(point) => write!(f, "Point {} is not on the board", point),
+            SgfError::TooDeep => write!(f, "The variations are nested too deep"),
+        }
+    }
+}
+
+/// Which position of the game to start from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Load {
+    /// The setup stones (AB/AW/AE) before the first move.
+    Setup,
+    /// The position after the last move of the main variation.
+    FinalPosition,
+}
+
+type Node = Vec<(String, Vec<String>)>;
+
+/// How deep variations can nest. The parser recurses for each level.
+pub const MAX_DEPTH: usize = 1000;
+
+/// Loads a position from an SGF file. Black is color 1 and white is color 2.
+pub fn load_position(text: &str, load: Load) -> Result<StartPosition, SgfError> {
+    let nodes = Parser::new(text).main_variation()?;
+    let root = nodes.first().ok_or(SgfError::Empty)?;
+
+    let size = match property(root, "SZ") {
+        Some(size) => parse_size(size)?,
+        None => (19, 19),
+    };
+    let mut board = Board::empty(size.0 as _, size.1 as _, false);
+    let mut to_move = Color(1);
+
+    for node in &nodes {
+        let moved = property(node, "B")
+            .map(|p| (p, Color(1)))
+            .or_else(|| property(node, "W").map(|p| (p, Color(2))));
+
+        if let Some((point, color)) = moved {
+            if load == Load::Setup {
+                break;
+            }
+            if let Some(point) = parse_move(&board, point)? {
+                play(&mut board, point, color);
+            }
+            to_move = if color == Color(1) {
+                Color(2)
+            } else {
+                Color(1)
+            };
+        }
+
+        for (name, color) in &[("AE", Color::empty()), ("AB", Color(1)), ("AW", Color(2))] {
+            for value in values(node, name) {
+                for point in parse_points(&board, value)? {
+                    board.set_point(point, *color);
+                }
+            }
+        }
+
+        match property(node, "PL") {
+            Some("B") | Some("b") => to_move = Color(1),
+            Some("W") | Some("w") => to_move = Color(2),
+            _ => {}
+        }
+    }
+
+    Ok(StartPosition {
+        size,
+        points: board.points.iter().map(|c| c.0).collect(),
+        to_move: to_move.0,
+    })
+}
+
+fn values<'a>(node: &'a Node, name: &'a str) -> impl Iterator<Item = &'a str> {
+    node.iter()
+        .filter(move |(n, _)| n == name)
+        .flat_map(|(_, values)| values.iter().map(|v| v.as_str()))
+}
+
+fn property<'a>(node: &'a Node, name: &'a str) -> Option<&'a str> {
+    values(node, name).next()
+}
+
+fn parse_size(value: &str) -> Result<(u8, u8), SgfError> {
+    let error = || SgfError::BoardSize(value.to_string());
+    let number = |s: &str| s.trim().parse::<u8>().ok().filter(|&n| n > 0 && n <= 52);
+
+    let size = match value.split_once(':') {
+        Some((w, h)) => (number(w), number(h)),
+        None => (number(value), number(value)),
+    };
+    match size {
+        (Some(w), Some(h)) => Ok((w, h)),
+        _ => Err(error()),
+    }
+}
+
+fn parse_point(board: &Board, value: &str) -> Result<Point, SgfError> {
+    let coord = |c: u8| match c {
+        b'a'..=b'z' => Some((c - b'a') as u32),
+        b'A'..=b'Z' => Some((c - b'A') as u32 + 26),
+        _ => None,
+    };
+
+    let point = match value.as_bytes() {
+        [x, y] => coord(*x).zip(coord(*y)),
+        _ => None,
+    };
+    point
+        .filter(|&p| board.point_within(p))
+        .ok_or_else(|| SgfError::Point(value.to_string()))
+}
+
+/// A move, or `None` for a pass.
+fn parse_move(board: &Board, value: &str) -> Result<Option<Point>, SgfError> {
+    // Older files use "tt" for passes on small boards
+    if value.is_empty() || (value == "tt" && board.width <= 19 && board.height <= 19) {
+        return Ok(None);
+    }
+    parse_point(board, value).map(Some)
+}
+
+/// A single point or a rectangle of points written as "aa:cc".
+fn parse_points(board: &Board, value: &str) -> Result<Vec<Point>, SgfError> {
+    let (a, b) = match value.split_once(':') {
+        Some((a, b)) => (parse_point(board, a)?, parse_point(board, b)?),
+        None => {
+            let p = parse_point(board, value)?;
+            (p, p)
+        }
+    };
+
+    let mut points = Vec::new();
+    for y in a.1.min(b.1)..=a.1.max(b.1) {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            points.push((x, y));
+        }
+    }
+    Ok(points)
+}
+
+/// Places a stone and removes the groups left without liberties, the player's own last.
+fn play(board: &mut Board, point: Point, color: Color) {
+    board.set_point(point, color);
+
+    let neighbors = board.surrounding_points(point).collect::<Vec<_>>();
+    for p in neighbors {
+        let other = board.get_point(p);
+        if !other.is_empty() && other != color {
+            capture_if_dead(board, p);
+        }
+    }
+    capture_if_dead(board, point);
+}
+
+fn capture_if_dead(board: &mut Board, point: Point) {
+    let color = board.get_point(point);
+    if color.is_empty() {
+        return;
+    }
+
+    let mut group = vec![point];
+    let mut i = 0;
+    while i < group.len() {
+        for p in board.surrounding_points(group[i]) {
+            let c = board.get_point(p);
+            if c.is_empty() {
+                return;
+            }
+            if c == color && !group.contains(&p) {
+                group.push(p);
+            }
+        }
+        i += 1;
+    }
+
+    for p in group {
+        board.set_point(p, Color::empty());
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        Parser {
+            text: text.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while self.text.get(self.pos)?.is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SgfError> {
+        match self.peek() {
+            Some(x) if x == c => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(_) => Err(SgfError::Syntax(self.pos)),
+            None => Err(SgfError::UnexpectedEnd),
+        }
+    }
+
+    /// Nodes of the first game in the file, following the first variation.
+    fn main_variation(&mut self) -> Result<Vec<Node>, SgfError> {
+        // Skip anything before the game, like mail headers
+        while let Some(c) = self.peek() {
+            if c == b'(' {
+                break;
+            }
+            self.pos += 1;
+        }
+
+        let mut nodes = Vec::new();
+        self.game_tree(&mut nodes, true, 0)?;
+        Ok(nodes)
+    }
+
+    fn game_tree(
+        &mut self,
+        nodes: &mut Vec<Node>,
+        main: bool,
+        depth: usize,
+    ) -> Result<(), SgfError> {
+        if depth >= MAX_DEPTH {
+            return Err(SgfError::TooDeep);
+        }
+        self.expect(b'(')?;
+        while self.peek() == Some(b';') {
+            self.pos += 1;
+            let node = self.node()?;
+            if main {
+                nodes.push(node);
+            }
+        }
+
+        let mut first = true;
+        while self.peek() == Some(b'(') {
+            self.game_tree(nodes, main && first, depth + 1)?;
+            first = false;
+        }
+        self.expect(b')')
+    }
+
+    fn node(&mut self) -> Result<Node, SgfError> {
+        let mut node = Vec::new();
+
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            let start = self.pos;
+            while self.text.get(self.pos).is_some_and(u8::is_ascii_alphabetic) {
+                self.pos += 1;
+            }
+            // Old files may have lowercase letters in the names
+            let name = self.text[start..self.pos]
+                .iter()
+                .filter(|c| c.is_ascii_uppercase())
+                .map(|&c| c as char)
+                .collect::<String>();
+
+            let mut values = Vec::new();
+            while self.peek() == Some(b'[') {
+                values.push(self.value()?);
+            }
+            if values.is_empty() {
+                return Err(self
+                    .peek()
+                    .map_or(SgfError::UnexpectedEnd, |_| SgfError::Syntax(self.pos)));
+            }
+            node.push((name, values));
+        }
+
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<String, SgfError> {
+        self.expect(b'[')?;
+        let mut value = Vec::new();
+        loop {
+            let c = *self.text.get(self.pos).ok_or(SgfError::UnexpectedEnd)?;
+            self.pos += 1;
+            match c {
+                b']' => break,
+                b'\\' => {
+                    let c = *self.text.get(self.pos).ok_or(SgfError::UnexpectedEnd)?;
+                    self.pos += 1;
+                    value.push(c);
+                }
+                _ => value.push(c),
+            }
+        }
+        Ok(String::from_utf8_lossy(&value).into_owned())
+    }
+}
@@ -0,0 +1,182 @@
+//! Snapshots of the whole game state, to load games without replaying them.
+//!
+//! Replaying every action through the rules gets slow in long games, so a snapshot of the
+//! state can be stored next to the replay. The replay stays the source of truth: the actions
+//! played after the snapshot was taken are replayed on top of it, and games without a usable
+//! snapshot are replayed like before.
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Board, Color, Game, GameReplay, GameState, GroupTracker, GroupVec, History, LoadError, Point,
+    Seat, SharedState, Visibility,
+};
+use crate::states::play::traitor::TraitorState;
+
+/// Changes whenever the snapshot format or anything in it does. Snapshots of other versions
+/// are ignored.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Actions between the snapshots the server stores. Writing one after every action would
+/// encode the whole history each time.
+pub const SNAPSHOT_INTERVAL: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    format: u32,
+    /// Replay version of the game.
+    version: u32,
+    /// Number of actions played when the snapshot was taken.
+    action_count: usize,
+    seed: u64,
+
+    state: GameState,
+    state_stack: Vec<GameState>,
+    seats: GroupVec<Seat>,
+    points: GroupVec<i32>,
+    turn: usize,
+    pass_count: usize,
+    board: Board,
+    /// Visibility as the values of the bitmaps.
+    board_visibility: Option<Board<u16>>,
+    board_history: History,
+    traitor: Option<TraitorState>,
+    eliminated: GroupVec<Color>,
+    kings: GroupVec<Option<Point>>,
+    resign_votes: GroupVec<bool>,
+}
+
+impl Game {
+    /// The state of the game, to be stored next to the replay from `dump`.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let shared = &self.shared;
+        let snapshot = Snapshot {
+            format: SNAPSHOT_VERSION,
+            version: shared.version,
+            action_count: self.actions.len(),
+            seed: self.seed,
+            state: self.state.clone(),
+            state_stack: self.state_stack.clone(),
+            seats: shared.seats.clone(),
+            points: shared.points.clone(),
+            turn: shared.turn,
+            pass_count: shared.pass_count,
+            board: shared.board.clone(),
+            board_visibility: shared
+                .board_visibility
+                .as_ref()
+                .map(|b| b.map(Visibility::into_value)),
+            board_history: shared.board_history.clone(),
+            traitor: shared.traitor.clone(),
+            eliminated: shared.eliminated.clone(),
+            kings: shared.kings.clone(),
+            resign_votes: shared.resign_votes.clone(),
+        };
+
+        let mut vec = Vec::new();
+        serde_cbor::to_writer(&mut vec, &snapshot).expect("Snapshot serialization failed");
+        vec
+    }
+
+    /// Loads a game from its replay and a snapshot taken of it, only playing the actions after
+    /// the snapshot. `None` if the snapshot is newer than the replay or of another format.
+    pub fn load_snapshot(dump: &[u8], snapshot: &[u8]) -> Result<Option<Game>, LoadError> {
+        let mut replay: GameReplay =
+            serde_cbor::from_slice(dump).map_err(|e| LoadError::Decode(e.to_string()))?;
+        // Clocks aren't replayed either, see `Game::load`
+        replay.mods.clock = None;
+
+        let snapshot: Snapshot =
+            serde_cbor::from_slice(snapshot).map_err(|e| LoadError::Decode(e.to_string()))?;
+        if snapshot.format != SNAPSHOT_VERSION
+            || snapshot.version != replay.version
+            || snapshot.action_count > replay.actions.len()
+            || snapshot.seed != replay.seed
+        {
+            return Ok(None);
+        }
+
+        let board = &snapshot.board;
+        let visibility_fits = match &snapshot.board_visibility {
+            Some(v) => {
+                (v.width, v.height) == (board.width, board.height)
+                    && v.points.len() == board.points.len()
+            }
+            None => true,
+        };
+        let seat_count = snapshot.seats.len();
+        let last_state = snapshot.board_history.last().map(|h| &h.state);
+        let states_fit = std::iter::once(&snapshot.state)
+            .chain(&snapshot.state_stack)
+            .chain(last_state)
+            .all(|state| fits_seats(state, seat_count));
+        if snapshot.points.len() != replay.komis.len()
+            || snapshot.board_history.is_empty()
+            || !board.fits_points()
+            || !visibility_fits
+            || board.hash() != board.full_hash()
+            || snapshot.turn >= seat_count
+            || snapshot.resign_votes.len() != seat_count
+            || !states_fit
+        {
+            return Err(LoadError::Decode(
+                "snapshot doesn't fit the game".to_string(),
+            ));
+        }
+
+        let action_count = snapshot.action_count;
+        let mut actions = replay.actions;
+        let later = actions.split_off(action_count);
+        let mut game = Game {
+            state: snapshot.state,
+            state_stack: snapshot.state_stack,
+            shared: SharedState {
+                seats: snapshot.seats,
+                points: snapshot.points,
+                turn: snapshot.turn,
+                pass_count: snapshot.pass_count,
+                board: snapshot.board,
+                board_visibility: snapshot
+                    .board_visibility
+                    .map(|b| b.map(Visibility::from_value)),
+                board_history: snapshot.board_history,
+                komis: replay.komis,
+                mods: replay.mods,
+                clock: None,
+                traitor: snapshot.traitor,
+                eliminated: snapshot.eliminated,
+                kings: snapshot.kings,
+                resign_votes: snapshot.resign_votes,
+                groups: GroupTracker::default(),
+                version: replay.version,
+            },
+            actions,
+            seed: replay.seed,
+            started: replay.started,
+        };
+        game.apply_actions(later)
+            .map_err(|idx| LoadError::Action(action_count + idx))?;
+        Ok(Some(game))
+    }
+
+    /// Loads from the snapshot if there is a usable one, otherwise replays the whole game.
+    pub fn load_with_snapshot(dump: &[u8], snapshot: Option<&[u8]>) -> Result<Game, LoadError> {
+        if let Some(snapshot) = snapshot {
+            if let Ok(Some(game)) = Game::load_snapshot(dump, snapshot) {
+                return Ok(game);
+            }
+        }
+        Game::load(dump)
+    }
+}
+
+/// Whether the per seat parts of the state have a value for every seat.
+fn fits_seats(state: &GameState, seat_count: usize) -> bool {
+    match state {
+        GameState::Play(play) => {
+            play.players_passed.len() == seat_count && play.draw_proposed.len() == seat_count
+        }
+        GameState::Scoring(scoring) => scoring.players_accepted.len() == seat_count,
+        _ => true,
+    }
+}
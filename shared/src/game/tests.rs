@@ -7,7 +7,7 @@ fn seats() {
         GroupVec::from(&[0, 15][..]),
         (9, 9),
         GameModifier::default(),
-        0
+        0,
     )
     .unwrap();
 
@@ -55,15 +55,1533 @@ fn seats() {
     assert_eq!(game.leave_seat(300, 1), Err(TakeSeatError::NotOpen));
 }
 
+#[test]
+fn setup_errors() {
+    let mods = GameModifier {
+        zen_go: Some(ZenGo { color_count: 3 }),
+        hidden_move: Some(HiddenMoveGo {
+            placement_count: 3,
+            teams_share_stones: false,
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+
+    let errors = Game::standard(&[1, 3], komis, (9, 9), mods, 0)
+        .err()
+        .unwrap();
+    assert_eq!(
+        errors,
+        vec![
+            SetupError::Conflict(setup::Modifier::ZenGo, setup::Modifier::HiddenMove),
+            SetupError::SeatTeam { seat: 1, team: 3 },
+            SetupError::Invalid {
+                modifier: setup::Modifier::ZenGo,
+                reason: "color count doesn't match the komis".into(),
+            },
+        ]
+    );
+
+    for &color in &[0, 3] {
+        let mods = GameModifier {
+            visibility_mode: Some(VisibilityMode::OneColor(OneColorChoice::Fixed(Color(
+                color,
+            )))),
+            ..GameModifier::default()
+        };
+        let komis = GroupVec::from(&[0, 15][..]);
+        assert_eq!(
+            Game::standard(&[1, 2], komis, (9, 9), mods, 0).err(),
+            Some(vec![SetupError::Invalid {
+                modifier: setup::Modifier::OneColor,
+                reason: "stones must be shown as one of the colors in the game".into(),
+            }])
+        );
+    }
+}
+
+#[test]
+fn zen_go() {
+    // Two players rotating through three colors
+    let mods = GameModifier {
+        zen_go: Some(ZenGo::default()),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 0, 0][..]);
+    let mut game = Game::standard(&[1, 1], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let moves = [(100, 0, 0), (200, 1, 0), (100, 2, 0), (200, 3, 0)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+
+    let row = (0..4)
+        .map(|x| game.shared.board.get_point((x, 0)).0)
+        .collect::<Vec<_>>();
+    assert_eq!(row, vec![1, 2, 3, 1]);
+    assert!(game.shared.seats.iter().all(|s| s.team == Color(2)));
+}
+
+#[test]
+fn neutral_stones() {
+    let mods = GameModifier {
+        neutral_stones: Some(NeutralStones {
+            count: 5,
+            capturable: false,
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods.clone(), 42).unwrap();
+    let again = Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 42).unwrap();
+
+    // The same seed always gives the same board
+    assert_eq!(game.shared.board, again.shared.board);
+
+    let neutral_count = game
+        .shared
+        .board
+        .points
+        .iter()
+        .filter(|c| c.is_neutral())
+        .count();
+    assert_eq!(neutral_count, 5);
+
+    // Neutral stones don't own territory
+    let scoring = ScoringState::new(&game.shared);
+    assert_eq!(scoring.scores, komis);
+
+    // Nor can they be marked dead
+    let mut game = game;
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    game.make_action(100, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    let board = &game.shared.board;
+    let idx = board.points.iter().position(|c| c.is_neutral()).unwrap();
+    let (x, y) = board.idx_to_coord(idx).unwrap();
+    game.make_action(100, ActionKind::Place(x, y), Millisecond(0))
+        .unwrap();
+    match &game.state {
+        GameState::Scoring(scoring) => {
+            assert!(scoring.groups.iter().all(|g| g.alive));
+            assert_eq!(scoring.scores, komis);
+        }
+        _ => panic!("game should be in scoring"),
+    }
+}
+
+#[test]
+fn last_survivor() {
+    let mods = GameModifier {
+        last_survivor: Some(LastSurvivor {
+            grace_moves: 4,
+            min_stones: 2,
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 0, 0][..]);
+
+    // Needs at least three teams
+    assert!(Game::standard(
+        &[1, 2],
+        GroupVec::from(&[0, 0][..]),
+        (9, 9),
+        mods.clone(),
+        0
+    )
+    .is_err());
+
+    let mut game = Game::standard(&[1, 2, 3], komis, (9, 9), mods, 0).unwrap();
+    for (seat, player) in [100, 200, 300].iter().enumerate() {
+        game.take_seat(*player, seat).unwrap();
+    }
+
+    let moves = [
+        (100, 0, 0),
+        (200, 8, 8),
+        (300, 4, 4),
+        (100, 0, 2),
+        (200, 8, 6),
+    ];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+
+    // The third team only has one stone after the grace period
+    assert!(game.shared.seats[2].resigned);
+    assert_eq!(game.shared.eliminated, GroupVec::from(&[Color(3)][..]));
+    assert_eq!(game.shared.turn, 0);
+
+    // Undoing the move brings them back
+    game.make_action(100, ActionKind::Cancel, Millisecond(0))
+        .unwrap();
+    assert!(!game.shared.seats[2].resigned);
+    assert!(game.shared.eliminated.is_empty());
+}
+
+#[test]
+fn alliances() {
+    let mods = GameModifier {
+        alliances: Some(Alliances {
+            teams: vec![1, 1, 2],
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 0, 0][..]);
+
+    // Alliances have to match the teams
+    let mut bad_mods = mods.clone();
+    bad_mods.alliances = Some(Alliances { teams: vec![1, 2] });
+    assert!(Game::standard(&[1, 2, 3], komis.clone(), (9, 9), bad_mods, 0).is_err());
+
+    let mut game = Game::standard(&[1, 2, 3], komis, (9, 9), mods, 0).unwrap();
+    for (seat, player) in [100, 200, 300].iter().enumerate() {
+        game.take_seat(*player, seat).unwrap();
+    }
+
+    let moves = [(100, 0, 0), (200, 1, 0), (300, 0, 1)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+
+    // The corner stone shares liberties with its ally and survives
+    assert_eq!(game.shared.board.get_point((0, 0)), Color(1));
+    assert_eq!(game.shared.board.get_point((1, 0)), Color(2));
+
+    // Scoring keeps the allied stones together, each counted for its own team
+    for &player in &[100, 200, 300] {
+        game.make_action(player, ActionKind::Pass, Millisecond(0))
+            .unwrap();
+    }
+    let scoring = |game: &Game| match &game.state {
+        GameState::Scoring(scoring) => scoring.clone(),
+        state => panic!("Game should be in scoring, was {:?}", state),
+    };
+    assert_eq!(scoring(&game).points.get_point((1, 0)), Color(2));
+
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    let scoring = scoring(&game);
+    let group = scoring
+        .groups
+        .iter()
+        .find(|g| g.points.contains(&(0, 0)))
+        .unwrap();
+    assert!(group.points.contains(&(1, 0)));
+    assert!(!group.alive);
+}
+
+#[test]
+fn king() {
+    let mods = GameModifier {
+        king: Some(KingGo {}),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods.clone(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let moves = [(100, 0, 0), (200, 1, 0), (100, 4, 4), (200, 0, 1)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+
+    // Black's king was captured, and black lost whatever the score
+    match &game.state {
+        GameState::Done(scoring) => assert_eq!(scoring.lost, GroupVec::from(&[Color(1)][..])),
+        _ => panic!("game should be over"),
+    }
+    assert!(game.shared.seats[0].resigned);
+    assert_eq!(
+        game.shared.kings,
+        GroupVec::from(&[Some((0, 0)), Some((1, 0))][..])
+    );
+
+    // Hidden stones are crowned once they are on the board
+    let hidden = GameModifier {
+        hidden_move: Some(HiddenMoveGo {
+            placement_count: 2,
+            teams_share_stones: false,
+        }),
+        ..mods.clone()
+    };
+    let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), hidden, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    // The first white stone collides with a black one and is removed
+    let moves = [(100, 6, 6), (100, 2, 2), (200, 6, 6), (200, 3, 3)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+    game.make_action(100, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Play(_)));
+    assert_eq!(
+        game.shared.kings,
+        GroupVec::from(&[Some((2, 2)), Some((3, 3))][..])
+    );
+
+    // With phantom go, you only know where your own king is
+    let mods = GameModifier {
+        phantom: Some(PhantomGo {}),
+        ..mods
+    };
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Place(8, 8), Millisecond(0))
+        .unwrap();
+
+    assert_eq!(
+        game.get_view(100).kings,
+        GroupVec::from(&[Some((0, 0)), None][..])
+    );
+    assert_eq!(
+        game.get_view(200).kings,
+        GroupVec::from(&[None, Some((8, 8))][..])
+    );
+}
+
+#[test]
+fn shape_go() {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let pixel = GameModifier {
+        pixel: true,
+        ..GameModifier::default()
+    };
+    let shape = GameModifier {
+        shape: Some(ShapeGo::pixel()),
+        ..GameModifier::default()
+    };
+
+    // Pixel go is the same as shape go with the 2x2 preset
+    let mut boards = Vec::new();
+    for mods in [pixel, shape] {
+        let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+        game.take_seat(100, 0).unwrap();
+        game.take_seat(200, 1).unwrap();
+        game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+            .unwrap();
+        game.make_action(200, ActionKind::Place(4, 4), Millisecond(0))
+            .unwrap();
+        boards.push(game.shared.board);
+    }
+    assert_eq!(boards[0], boards[1]);
+    assert_eq!(boards[0].get_point((3, 3)), Color(2));
+    assert_eq!(boards[0].get_point((4, 4)), Color(2));
+
+    let mods = GameModifier {
+        shape: Some(ShapeGo {
+            shapes: vec![Shape {
+                points: vec![(0, 0), (1, 0), (0, 1)],
+            }],
+            allow_rotation: true,
+        }),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let choice = ShapeChoice {
+        shape: 0,
+        rotation: 1,
+    };
+    game.make_action(100, ActionKind::PlaceShape(4, 4, choice), Millisecond(0))
+        .unwrap();
+    for point in [(4, 4), (4, 5), (3, 4)] {
+        assert_eq!(game.shared.board.get_point(point), Color(1));
+    }
+
+    let choice = ShapeChoice {
+        shape: 1,
+        rotation: 0,
+    };
+    assert_eq!(
+        game.make_action(200, ActionKind::PlaceShape(0, 0, choice), Millisecond(0)),
+        Err(MakeActionError::Illegal)
+    );
+
+    // Only pixel go can be placed past the edge of the board
+    let choice = ShapeChoice::default();
+    assert_eq!(
+        game.make_action(200, ActionKind::PlaceShape(9, 0, choice), Millisecond(0)),
+        Err(MakeActionError::OutOfBounds)
+    );
+    game.make_action(200, ActionKind::PlaceShape(8, 0, choice), Millisecond(0))
+        .unwrap();
+    assert_eq!(game.shared.board.get_point((8, 0)), Color(2));
+
+    let shapes = |points: Vec<(i8, i8)>| GameModifier {
+        shape: Some(ShapeGo {
+            shapes: vec![Shape { points }],
+            allow_rotation: false,
+        }),
+        ..GameModifier::default()
+    };
+    let errors = |mods| {
+        Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 0)
+            .err()
+            .unwrap()
+    };
+    assert_eq!(
+        errors(shapes(vec![(0, 0), (0, 0)])),
+        vec![SetupError::Invalid {
+            modifier: setup::Modifier::Shape,
+            reason: "shapes can't cover a point twice".into(),
+        }]
+    );
+    assert_eq!(
+        errors(shapes(vec![(0, 0), (1, 1)])),
+        vec![SetupError::Invalid {
+            modifier: setup::Modifier::Shape,
+            reason: "shapes must be connected".into(),
+        }]
+    );
+}
+
 use insta::{assert_debug_snapshot, glob};
+use std::collections::HashMap;
 use std::fs;
 
+#[test]
+fn phantom_pixel_atari() {
+    let mods = GameModifier {
+        pixel: true,
+        phantom: Some(PhantomGo {}),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    game.make_action(100, ActionKind::Place(1, 1), Millisecond(0))
+        .unwrap();
+    assert_debug_snapshot!("phantom_pixel_hidden", game.get_view(200));
+
+    // Black has two liberties left, but a single blob fills both of them.
+    game.make_action(200, ActionKind::Place(3, 1), Millisecond(0))
+        .unwrap();
+    assert_debug_snapshot!("phantom_pixel_atari", game.get_view(200));
+}
+
+#[test]
+fn phantom_shape_atari_at_edge() {
+    // Black has the two liberties on the top edge, but the L can only cover both from
+    // above the board.
+    let mut points = vec![0; 81];
+    points[9 + 2] = 1;
+    for &(x, y) in &[(0, 1), (3, 1), (0, 2), (3, 2), (1, 3), (2, 3)] {
+        points[y * 9 + x] = 2;
+    }
+    let mods = GameModifier {
+        shape: Some(ShapeGo {
+            shapes: vec![Shape {
+                points: vec![(0, 0), (0, 1), (1, 1)],
+            }],
+            allow_rotation: false,
+        }),
+        phantom: Some(PhantomGo {}),
+        start_position: Some(StartPosition {
+            size: (9, 9),
+            points,
+            to_move: 1,
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    game.make_action(
+        100,
+        ActionKind::PlaceShape(1, 1, ShapeChoice::default()),
+        Millisecond(0),
+    )
+    .unwrap();
+    assert_hidden(&game, 200, &[(1, 1), (1, 2), (2, 2)]);
+}
+
+/// Checks that nothing `player_id` receives tells where the given stones are.
+fn assert_hidden(game: &Game, player_id: u64, hidden: &[Point]) {
+    let width = game.shared.board.width;
+    let idx = |&(x, y): &Point| (y * width + x) as usize;
+
+    let view = game.get_view(player_id);
+    for p in hidden {
+        assert!(
+            view.board[idx(p)].is_empty(),
+            "{:?} visible on the board",
+            p
+        );
+        if let Some(visibility) = &view.board_visibility {
+            assert_eq!(visibility[idx(p)], 0, "{:?} visible in visibility", p);
+        }
+        if let GameStateView::Play(play) = &view.state {
+            assert!(!play.last_stone.iter().flatten().any(|s| s == p));
+        }
+        assert!(!view.kings.iter().any(|k| k.as_ref() == Some(p)));
+    }
+
+    for turn in 0..game.shared.board_history.len() as u32 {
+        let history = match game.get_view_at(player_id, turn) {
+            Some(history) => history,
+            None => continue,
+        };
+        for p in hidden {
+            assert_eq!(history.board[idx(p)], 0, "{:?} visible at move {}", p, turn);
+            assert!(!history.last_stone.iter().flatten().any(|s| s == p));
+        }
+    }
+}
+
+#[test]
+fn hidden_stones_stay_hidden() {
+    let komis = GroupVec::from(&[0, 15][..]);
+
+    let mods = GameModifier {
+        phantom: Some(PhantomGo {}),
+        king: Some(KingGo {}),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    let moves = [(100, 2, 2), (200, 6, 6), (100, 2, 6), (200, 6, 2)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+    assert_hidden(&game, 200, &[(2, 2), (2, 6)]);
+    assert_hidden(&game, 100, &[(6, 6), (6, 2)]);
+
+    // The last move is marked for the player who made it
+    let last_stone = |player_id| match game.get_view(player_id).state {
+        GameStateView::Play(play) => play.last_stone,
+        _ => unreachable!(),
+    };
+    assert_eq!(last_stone(200), Some(GroupVec::from(&[(6, 2)][..])));
+    assert_eq!(last_stone(100), None);
+    assert_eq!(
+        game.get_view_at(100, 3).unwrap().last_stone,
+        Some(GroupVec::from(&[(2, 6)][..]))
+    );
+    assert_eq!(game.get_view_at(200, 3).unwrap().last_stone, None);
+
+    let mods = GameModifier {
+        hidden_move: Some(HiddenMoveGo {
+            placement_count: 1,
+            teams_share_stones: false,
+        }),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    game.make_action(100, ActionKind::Place(2, 2), Millisecond(0))
+        .unwrap();
+    assert_hidden(&game, 200, &[(2, 2)]);
+    game.make_action(200, ActionKind::Place(6, 6), Millisecond(0))
+        .unwrap();
+    game.make_action(100, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Play(_)));
+
+    game.make_action(100, ActionKind::Place(4, 4), Millisecond(0))
+        .unwrap();
+    assert_hidden(&game, 200, &[(2, 2)]);
+    assert_hidden(&game, 100, &[(6, 6)]);
+}
+
+#[test]
+fn scoring_view_shows_only_the_board() {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mods = GameModifier {
+        phantom: Some(PhantomGo {}),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    let moves = [(100, 2, 2), (200, 6, 6), (100, 2, 3), (200, 6, 5)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+    game.make_action(100, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Scoring(_)));
+
+    for &player in &[100, 200, 0] {
+        let view = game.get_view(player);
+        assert_eq!(view.board, game.shared.board.points);
+        assert_eq!(view.board_visibility, None);
+        let scoring = match view.state {
+            GameStateView::Scoring(scoring) => scoring,
+            _ => unreachable!(),
+        };
+        for group in &scoring.groups {
+            for &p in &group.points {
+                let idx = (p.1 * 9 + p.0) as usize;
+                assert_eq!(view.board[idx], group.team);
+            }
+        }
+    }
+}
+
 #[test]
 fn replay_snapshots() {
     glob!("replays/*.txt", |path| {
         let input = fs::read(path).unwrap();
         let game = Game::load(&input).unwrap();
+        assert_eq!(game.consistency_errors(), Vec::<String>::new());
         let view = game.get_view(0);
         assert_debug_snapshot!(view);
     });
 }
+
+#[test]
+fn seed_commitment() {
+    assert_eq!(
+        fairness::seed_commitment(1234),
+        "1af2444c165b8d6156651aa4f8dc49e6302f690473e80304fdfdb73baa9140c7"
+    );
+    assert!(fairness::verify_seed(
+        1234,
+        &fairness::seed_commitment(1234)
+    ));
+    assert!(!fairness::verify_seed(
+        1235,
+        &fairness::seed_commitment(1234)
+    ));
+
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 1234).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let view = game.get_view(100);
+    assert_eq!(view.seed_commitment, fairness::seed_commitment(1234));
+    assert!(!export::sgf_export(&game, &HashMap::new()).contains("1234"));
+
+    game.make_action(100, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+
+    match game.get_view(200).state {
+        GameStateView::Done(done) => assert_eq!(done.seed, 1234),
+        state => panic!("Game should be over, was {:?}", state),
+    }
+    assert!(export::sgf_export(&game, &HashMap::new()).contains("Seed: 1234"));
+}
+
+#[test]
+fn one_color_legacy() {
+    #[derive(Serialize)]
+    enum LegacyMode {
+        OneColor,
+    }
+
+    for packed in &[false, true] {
+        let encode = |value: &Option<LegacyMode>| {
+            let mut vec = Vec::new();
+            let mut serializer = serde_cbor::Serializer::new(&mut vec);
+            if *packed {
+                serializer = serializer.packed_format();
+            }
+            value.serialize(&mut serializer).unwrap();
+            vec
+        };
+        let mode: Option<VisibilityMode> =
+            serde_cbor::from_slice(&encode(&Some(LegacyMode::OneColor))).unwrap();
+        assert_eq!(
+            mode,
+            Some(VisibilityMode::OneColor(OneColorChoice::Fixed(Color(2))))
+        );
+    }
+
+    for mode in &[
+        VisibilityMode::OneColor(OneColorChoice::Fixed(Color(1))),
+        VisibilityMode::OneColor(OneColorChoice::Random),
+    ] {
+        let mut packed = Vec::new();
+        mode.serialize(&mut serde_cbor::Serializer::new(&mut packed).packed_format())
+            .unwrap();
+        for vec in &[packed, serde_cbor::to_vec(mode).unwrap()] {
+            assert_eq!(
+                &serde_cbor::from_slice::<VisibilityMode>(vec).unwrap(),
+                mode
+            );
+        }
+    }
+}
+
+#[test]
+fn move_limit_and_draw() {
+    let mods = GameModifier {
+        move_limit: Some(MoveLimit { moves: 3 }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Play(_)));
+    game.make_action(100, ActionKind::Place(1, 1), Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Scoring(_)));
+
+    // A draw needs everyone to agree before the next stone
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    game.make_action(200, ActionKind::ProposeDraw, Millisecond(0))
+        .unwrap();
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(100, ActionKind::ProposeDraw, Millisecond(0))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Play(_)));
+
+    game.make_action(200, ActionKind::ProposeDraw, Millisecond(0))
+        .unwrap();
+    match &game.state {
+        GameState::Done(scoring) => assert!(scoring.draw),
+        state => panic!("Game should be over, was {:?}", state),
+    }
+}
+
+#[test]
+fn team_resign() {
+    let mods = GameModifier {
+        team_resign: Some(TeamResign::All),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2, 1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+    for (seat, player) in [100, 200, 300, 400].iter().enumerate() {
+        game.take_seat(*player, seat).unwrap();
+    }
+
+    // One teammate wanting to resign isn't enough
+    game.make_action(100, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+    let view = game.get_view(200);
+    assert!(view.seats.iter().all(|s| !s.resigned));
+    assert_eq!(&view.resign_votes[..], &[true, false, false, false]);
+
+    // Votes can be cast out of turn, and undoing a move undoes them
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(400, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+    assert_eq!(&game.shared.resign_votes[..], &[true, false, false, true]);
+    game.make_action(200, ActionKind::Cancel, Millisecond(0))
+        .unwrap();
+    assert_eq!(&game.shared.resign_votes[..], &[false; 4]);
+
+    game.make_action(100, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(300, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+    let view = game.get_view(200);
+    let resigned = view.seats.iter().map(|s| s.resigned).collect::<Vec<_>>();
+    assert_eq!(resigned, vec![true, false, true, false]);
+    assert!(matches!(game.state, GameState::Done(_)));
+
+    // Without team resign only the player on turn can resign
+    let mut game =
+        Game::standard(&[1, 2], komis.clone(), (9, 9), GameModifier::default(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    assert_eq!(
+        game.make_action(200, ActionKind::Resign, Millisecond(0)),
+        Err(MakeActionError::NotTurn)
+    );
+
+    // Votes out of turn leave the clock of the player on turn alone
+    let mods = GameModifier {
+        team_resign: Some(TeamResign::All),
+        clock: Some(Clock {
+            rule: ClockRule::Fischer(clock::FischerClock {
+                main_time: Millisecond(60_000),
+                increment: Millisecond(5_000),
+            }),
+        }),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2, 1, 2], komis.clone(), (9, 9), mods, 0).unwrap();
+    for (seat, player) in [100, 200, 300, 400].iter().enumerate() {
+        game.take_seat(*player, seat).unwrap();
+    }
+    for (i, &player) in [100, 200, 300, 400].iter().enumerate() {
+        let time = Millisecond(1_000 * i as i128);
+        game.make_action(player, ActionKind::Place(i as u32, 0), time)
+            .unwrap();
+    }
+    let clock = game.shared.clock.clone().unwrap();
+    assert!(!clock.paused);
+    game.make_action(400, ActionKind::Resign, Millisecond(50_000))
+        .unwrap();
+    assert_eq!(game.shared.clock.as_ref().unwrap().clocks, clock.clocks);
+
+    // Any member resigning in scoring takes the whole team out
+    let mods = GameModifier {
+        team_resign: Some(TeamResign::Any),
+        ..GameModifier::default()
+    };
+    let mut game = Game::standard(&[1, 2, 1, 2], komis, (9, 9), mods, 0).unwrap();
+    for (seat, player) in [100, 200, 300, 400].iter().enumerate() {
+        game.take_seat(*player, seat).unwrap();
+    }
+    // Passing counts for the whole team
+    for &player in &[100, 200] {
+        game.make_action(player, ActionKind::Pass, Millisecond(0))
+            .unwrap();
+    }
+    assert!(matches!(game.state, GameState::Scoring(_)));
+
+    game.make_action(400, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+    assert!(game.shared.seats[1].resigned && game.shared.seats[3].resigned);
+    match &game.state {
+        GameState::Scoring(scoring) => {
+            assert_eq!(scoring.players_accepted, vec![false, true, false, true])
+        }
+        state => panic!("Game should be in scoring, was {:?}", state),
+    }
+}
+
+#[test]
+fn sgf_moves() {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let moves = [(100, 2, 2), (200, 6, 6), (100, 2, 6)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+    for &player in &[200, 100, 100, 200] {
+        game.make_action(player, ActionKind::Pass, Millisecond(0))
+            .unwrap();
+    }
+    assert!(matches!(game.state, GameState::Done(_)));
+
+    let nicks = [(100, "Alice".to_string()), (200, "Bob".to_string())]
+        .iter()
+        .cloned()
+        .collect();
+    game.started = Some(1_600_000_000);
+    let sgf = export::sgf_export(&game, &nicks);
+    assert!(sgf.contains("PB[Alice]PW[Bob]KM[7.5]DT[2020-09-13]"));
+    assert!(sgf.contains("RE[W+6.5]"));
+    assert!(sgf.ends_with(";B[cc];W[gg];B[cg];W[];B[])"));
+
+    // Pixel go can't be written as moves
+    let mods = GameModifier {
+        pixel: true,
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.make_action(100, ActionKind::Place(1, 1), Millisecond(0))
+        .unwrap();
+    let sgf = export::sgf_export(&game, &HashMap::new());
+    assert!(sgf.ends_with(";AB[aa]AB[ba]AB[ab]AB[bb])"));
+    assert!(sgf.contains("GC[Variants: pixel]"));
+
+    // The variants can be read back from their own property
+    let start = sgf.find("VG[").unwrap() + 3;
+    let mut json = String::new();
+    let mut chars = sgf[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => json.extend(chars.next()),
+            ']' => break,
+            c => json.push(c),
+        }
+    }
+    let mods: GameModifier = serde_json::from_str(&json).unwrap();
+    assert!(mods.pixel);
+}
+
+#[test]
+fn sgf_timeout() {
+    let mods = GameModifier {
+        clock: Some(Clock {
+            rule: ClockRule::Fischer(clock::FischerClock {
+                main_time: Millisecond(60_000),
+                increment: Millisecond(0),
+            }),
+        }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    game.make_action(100, ActionKind::Place(2, 2), Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Place(6, 6), Millisecond(1_000))
+        .unwrap();
+
+    // Black's clock runs out, and the move becomes a loss on time
+    game.make_action(100, ActionKind::Place(2, 6), Millisecond(100_000))
+        .unwrap();
+    assert!(matches!(game.state, GameState::Done(_)));
+    assert!(export::sgf_export(&game, &HashMap::new()).contains("RE[W+T]"));
+
+    // Replays don't have clocks, but remember the timeout
+    let loaded = Game::load(&game.dump()).unwrap();
+    assert!(matches!(loaded.state, GameState::Done(_)));
+    assert!(export::sgf_export(&loaded, &HashMap::new()).contains("RE[W+T]"));
+}
+
+#[test]
+fn sgf_start_position() {
+    use sgf::{load_position, Load};
+
+    // White captures the black stone at bb
+    let text = "(;GM[1]SZ[5]AB[bb][ee:de]AW[ba][ab](;B[dd];W[cb];B[ce];W[bc])(;B[aa]))";
+
+    let setup = load_position(text, Load::Setup).unwrap();
+    assert_eq!(setup.size, (5, 5));
+    assert_eq!(setup.to_move, 1);
+    assert_eq!(&setup.points[..7], &[0, 2, 0, 0, 0, 2, 1]);
+    assert_eq!(setup.points.iter().filter(|&&c| c == 1).count(), 3);
+
+    let position = load_position(text, Load::FinalPosition).unwrap();
+    assert_eq!(position.to_move, 1);
+    assert_eq!(position.points.iter().filter(|&&c| c == 1).count(), 4);
+    assert_eq!(position.points[5 + 1], 0);
+    assert_eq!(position.points[5 * 2 + 1], 2);
+
+    assert!(load_position("(;SZ[5]B[zz])", Load::FinalPosition).is_err());
+    assert!(load_position("(;SZ[5]AB[aa]", Load::Setup).is_err());
+    let nested = format!("(;SZ[5]{}{})", "(;".repeat(5000), ")".repeat(5000));
+    assert_eq!(
+        load_position(&nested, Load::Setup),
+        Err(sgf::SgfError::TooDeep)
+    );
+
+    let mods = GameModifier {
+        start_position: Some(position.clone()),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    assert!(Game::standard(&[1, 2], komis.clone(), (9, 9), mods.clone(), 0).is_err());
+
+    let mut game = Game::standard(&[1, 2], komis, (5, 5), mods, 0).unwrap();
+    assert_eq!(game.shared.turn, 0);
+    game.take_seat(100, 0).unwrap();
+    game.make_action(100, ActionKind::Place(4, 0), Millisecond(0))
+        .unwrap();
+
+    // The exported record starts from the same position
+    let sgf = export::sgf_export(&game, &HashMap::new());
+    let exported = load_position(&sgf, Load::FinalPosition).unwrap();
+    let points = game
+        .shared
+        .board
+        .points
+        .iter()
+        .map(|c| c.0)
+        .collect::<Vec<_>>();
+    assert_eq!(exported.points, points);
+    assert_eq!(exported.to_move, 2);
+
+    // Setup stones can leave a group without liberties
+    let dead = load_position("(;SZ[5]AB[aa]AW[ba][ab])", Load::Setup).unwrap();
+    let mods = GameModifier {
+        start_position: Some(dead),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    assert_eq!(
+        Game::standard(&[1, 2], komis, (5, 5), mods, 0).err(),
+        Some(vec![SetupError::Invalid {
+            modifier: setup::Modifier::StartPosition,
+            reason: "position has stones without liberties".into(),
+        }])
+    );
+}
+
+#[test]
+fn sgf_hidden_markers() {
+    let mods = GameModifier {
+        phantom: Some(PhantomGo {}),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    let moves = [(100, 1, 0), (200, 0, 0), (100, 4, 4)];
+    for &(player, x, y) in &moves {
+        game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .unwrap();
+    }
+
+    let sgf = export::sgf_export(&game, &HashMap::new());
+    // White plays into atari next to the hidden black stone, revealing both
+    assert!(sgf.contains(";B[ba]MA[ba]C[ba is hidden, seen by Black]"));
+    assert!(sgf.contains(";W[aa]TR[ba]C[ba was revealed]"));
+    assert!(sgf.ends_with(";B[ee]MA[ee]C[ee is hidden, seen by Black])"));
+}
+
+#[test]
+fn record_round_trip() {
+    let mods = GameModifier {
+        pixel: true,
+        toroidal: Some(ToroidalGo {}),
+        visibility_mode: Some(VisibilityMode::OneColor(OneColorChoice::Random)),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 0, 0][..]);
+    let mut game = Game::standard(&[1, 2, 3], komis, (9, 9), mods, 77).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    game.take_seat(300, 2).unwrap();
+    game.leave_seat(300, 2).unwrap();
+    game.take_seat(300, 2).unwrap();
+
+    game.make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    game.make_action(200, ActionKind::Pass, Millisecond(0))
+        .unwrap();
+    game.make_action(300, ActionKind::Place(4, 4), Millisecond(0))
+        .unwrap();
+    game.make_action(100, ActionKind::Resign, Millisecond(0))
+        .unwrap();
+
+    let record = game.export_record();
+    let imported = Game::import_record(&record).unwrap();
+    assert_eq!(imported.shared.board, game.shared.board);
+    assert_eq!(imported.shared.seats, game.shared.seats);
+    assert_eq!(imported.export_record(), record);
+
+    let mut newer = game.record();
+    newer.version = 1000;
+    assert_eq!(
+        Game::import_record(&serde_json::to_string(&newer).unwrap()).err(),
+        Some(LoadError::Version(1000))
+    );
+
+    // Black's point is taken
+    let mut broken = game.record();
+    broken.actions[7].action = ReplayActionKind::Play(ActionKind::Place(0, 0));
+    assert_eq!(
+        Game::import_record(&serde_json::to_string(&broken).unwrap()).err(),
+        Some(LoadError::Action(7))
+    );
+}
+
+#[test]
+fn replay_versions() {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mods = GameModifier {
+        zen_go: Some(ZenGo { color_count: 2 }),
+        team_resign: Some(TeamResign::Any),
+        ..GameModifier::default()
+    };
+    assert!(Game::standard(&[1, 2, 1], komis.clone(), (9, 9), mods.clone(), 0).is_err());
+
+    // Old games were never validated, so they load anyway
+    let mut game = Game::build(&[1, 2, 1], komis.clone(), (9, 9), mods.clone(), 0, 0);
+    game.take_seat(100, 0).unwrap();
+    game.make_action(100, ActionKind::Place(2, 2), Millisecond(0))
+        .unwrap();
+    let loaded = Game::load(&game.dump()).unwrap();
+    assert_eq!(loaded.shared.version, 0);
+    assert_eq!(loaded.shared.board, game.shared.board);
+
+    // Nor did seats have to play a color with a komi
+    let mut game = Game::build(
+        &[1, 3],
+        komis.clone(),
+        (9, 9),
+        GameModifier::default(),
+        0,
+        0,
+    );
+    game.take_seat(100, 0).unwrap();
+    game.make_action(100, ActionKind::Place(2, 2), Millisecond(0))
+        .unwrap();
+    assert!(Game::load(&game.dump()).is_ok());
+    game.shared.version = REPLAY_VERSION;
+    assert_eq!(
+        Game::load(&game.dump()).err(),
+        Some(LoadError::Setup(vec![SetupError::SeatTeam {
+            seat: 1,
+            team: 3
+        }]))
+    );
+
+    game.shared.version = REPLAY_VERSION;
+    assert!(matches!(Game::load(&game.dump()), Err(LoadError::Setup(_))));
+    game.shared.version = REPLAY_VERSION + 1;
+    assert_eq!(
+        Game::load(&game.dump()).err(),
+        Some(LoadError::Version(REPLAY_VERSION + 1))
+    );
+    assert!(matches!(Game::load(b"nope"), Err(LoadError::Decode(_))));
+
+    let game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+    assert_eq!(
+        Game::load(&game.dump()).unwrap().shared.version,
+        REPLAY_VERSION
+    );
+}
+
+/// Games recorded before versioning play by the rules they were played with.
+#[test]
+fn legacy_replays() {
+    // A corner stone that a pixel could capture, which version 1 shows as atari
+    let game = Game::load(include_bytes!("replays/legacy-phantom-pixel.txt")).unwrap();
+    assert_eq!(game.shared.version, 0);
+    assert_eq!(game.shared.board.get_point((0, 0)), Color(1));
+    assert_hidden(&game, 200, &[(0, 0)]);
+
+    let mods = game.shared.mods.clone();
+    let komis = game.shared.komis.clone();
+    let mut current = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    current.take_seat(100, 0).unwrap();
+    current
+        .make_action(100, ActionKind::Place(0, 0), Millisecond(0))
+        .unwrap();
+    assert_eq!(current.get_view(200).board[0], Color(1));
+
+    // Seat colors only had to be 1 to 4
+    let game = Game::load(include_bytes!("replays/legacy-seat-color.txt")).unwrap();
+    assert_eq!(game.shared.version, 0);
+    assert_eq!(game.shared.seats[1].team, Color(3));
+    assert_eq!(game.shared.board.get_point((6, 6)), Color(3));
+}
+
+#[test]
+fn zobrist_hash() {
+    let play = |moves: &[(u32, u32)]| {
+        let komis = GroupVec::from(&[0, 15][..]);
+        let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+        game.take_seat(100, 0).unwrap();
+        game.take_seat(200, 1).unwrap();
+        for (i, &(x, y)) in moves.iter().enumerate() {
+            let player = if i % 2 == 0 { 100 } else { 200 };
+            game.make_action(player, ActionKind::Place(x, y), Millisecond(0))
+                .unwrap();
+        }
+        game
+    };
+
+    // White captures the black stone at (1, 0)
+    let game = play(&[(1, 0), (0, 0), (8, 8), (2, 0), (8, 7), (1, 1)]);
+    let board = &game.shared.board;
+    assert_eq!(board.get_point((1, 0)), Color::empty());
+    assert_eq!(board.hash(), board.full_hash());
+    for history in &game.shared.board_history {
+        assert_eq!(history.hash, history.board.full_hash());
+    }
+
+    // The same stones in another order hash the same
+    let other = play(&[(8, 7), (2, 0), (8, 8), (1, 1), (4, 4), (0, 0)]);
+    let mut board = other.shared.board.clone();
+    board.set_point((4, 4), Color::empty());
+    assert_eq!(board.hash(), game.shared.board.hash());
+
+    assert_eq!(Board::<Color>::empty(9, 9, false).hash(), 0);
+
+    // Boards without the kept hash, like mapped ones, still equal the same stones
+    let mapped = game.shared.board.map(|c| c);
+    assert_ne!(mapped.hash(), game.shared.board.hash());
+    assert_eq!(mapped, game.shared.board);
+}
+
+#[test]
+fn group_tracker() {
+    use rand::prelude::*;
+    use rand_pcg::Lcg64Xsh32;
+
+    let sorted = |groups: Vec<Group>| {
+        let mut groups = groups
+            .into_iter()
+            .map(|g| {
+                let mut points = g.points.to_vec();
+                points.sort_unstable();
+                (points, g.liberties)
+            })
+            .collect::<Vec<_>>();
+        groups.sort_unstable();
+        groups
+    };
+
+    let alliances = Alliances {
+        teams: vec![1, 1, 2],
+    };
+    for &(width, height, toroidal, allied) in &[
+        (9, 9, false, false),
+        (5, 7, true, false),
+        (9, 9, false, true),
+        (2, 3, true, true),
+    ] {
+        let alliances = if allied { Some(&alliances) } else { None };
+        let friendly = |a: Color, b: Color| match alliances {
+            Some(alliances) => alliances.allied(a, b),
+            None => a == b,
+        };
+
+        let mut rng = Lcg64Xsh32::seed_from_u64(width as u64 * 100 + height as u64);
+        let mut board = Board::empty(width, height, toroidal);
+        let mut tracker = GroupTracker::default();
+        for _ in 0..500 {
+            for _ in 0..rng.gen_range(1, 4) {
+                let point = (rng.gen_range(0, width), rng.gen_range(0, height));
+                let color = match rng.gen_range(0, 5) {
+                    0 | 1 => Color::empty(),
+                    c => Color(c - 1),
+                };
+                board.set_point(point, color);
+            }
+
+            // Captures take whole groups off at once
+            for group in tracker.dead_groups(&board, alliances) {
+                assert_eq!(group.liberties, 0);
+                if rng.gen_bool(0.5) {
+                    for &point in &group.points {
+                        board.set_point(point, Color::empty());
+                    }
+                }
+            }
+
+            assert_eq!(
+                sorted(tracker.groups(&board, alliances)),
+                sorted(find_groups_by(&board, friendly))
+            );
+        }
+    }
+}
+
+#[test]
+fn history_keyframes() {
+    use rand::prelude::*;
+    use rand_pcg::Lcg64Xsh32;
+
+    let mut rng = Lcg64Xsh32::seed_from_u64(0);
+    let mut board = Board::empty(9, 9, false);
+    let mut visibility = VisibilityBoard::empty(9, 9, false);
+    let mut boards = Vec::new();
+    let mut history = History::default();
+
+    let mut step = |turn: usize, history: &mut History, boards: &mut Vec<(Board, _)>| {
+        let point = (rng.gen_range(0, 9), rng.gen_range(0, 9));
+        board.set_point(point, Color(rng.gen_range(0, 3)));
+        visibility.point_mut(point).set(rng.gen_range(0, 3), true);
+        history.push(BoardHistory {
+            hash: board.hash(),
+            board: board.clone(),
+            board_visibility: Some(visibility.clone()),
+            state: GameState::play(2),
+            points: GroupVec::new(),
+            turn,
+            traitor: None,
+            eliminated: GroupVec::new(),
+            kings: GroupVec::new(),
+            resign_votes: GroupVec::new(),
+        });
+        boards.push((board.clone(), visibility.clone()));
+    };
+
+    for turn in 0..100 {
+        step(turn, &mut history, &mut boards);
+    }
+    for _ in 0..40 {
+        let popped = history.pop().unwrap();
+        assert_eq!(popped.board, boards.pop().unwrap().0);
+    }
+    for turn in 60..150 {
+        step(turn, &mut history, &mut boards);
+    }
+
+    assert_eq!(history.len(), boards.len());
+    for (idx, (board, visibility)) in boards.iter().enumerate() {
+        let restored = history.get(idx).unwrap();
+        assert_eq!(&restored.board, board);
+        assert_eq!(restored.board_visibility.as_ref(), Some(visibility));
+    }
+    let iterated = history.iter().map(|h| h.board).collect::<Vec<_>>();
+    assert!(iterated.iter().eq(boards.iter().map(|(b, _)| b)));
+    assert!(history.get(boards.len()).is_none());
+
+    let len = boards.len();
+    assert!(history.repeats(&boards[len - 1].0, 1));
+    assert!(history.repeats(&boards[len - 30].0, 30));
+    let mut other = Board::empty(9, 9, false);
+    other.set_point((0, 0), Color(5));
+    assert!(!history.repeats(&other, len));
+}
+
+#[test]
+fn history_states() {
+    use rand::prelude::*;
+    use rand_pcg::Lcg64Xsh32;
+
+    let mods = GameModifier {
+        traitor: Some(TraitorGo { traitor_count: 10 }),
+        ..GameModifier::default()
+    };
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    // The traitor state has no equality, so compare it encoded
+    let moment = |history: &BoardHistory| {
+        (
+            history.state.clone(),
+            serde_cbor::to_vec(&history.traitor).unwrap(),
+        )
+    };
+    let mut moments = vec![moment(game.shared.board_history.last().unwrap())];
+    let mut rng = Lcg64Xsh32::seed_from_u64(0);
+    while moments.len() < 80 {
+        let player = [100, 200][game.shared.turn];
+        let action = match rng.gen_range(0, 20) {
+            0 => ActionKind::Pass,
+            1 if moments.len() > 1 => ActionKind::Cancel,
+            _ => ActionKind::Place(rng.gen_range(0, 9), rng.gen_range(0, 9)),
+        };
+        if game.make_action(player, action, Millisecond(0)).is_err() {
+            continue;
+        }
+        let history = &game.shared.board_history;
+        moments.truncate(history.len());
+        if history.len() > moments.len() {
+            moments.push(moment(history.last().unwrap()));
+        }
+        if !matches!(game.state, GameState::Play(_)) {
+            break;
+        }
+    }
+
+    let history = &game.shared.board_history;
+    assert_eq!(history.len(), moments.len());
+    for (idx, restored) in history.iter().enumerate() {
+        assert_eq!(moment(&restored), moments[idx], "move {}", idx);
+        assert_eq!(moment(&history.get(idx).unwrap()), moments[idx]);
+    }
+    // Far less than a whole state for every move
+    let whole = std::mem::size_of::<GameState>() + std::mem::size_of::<Option<TraitorState>>();
+    assert!(history.state_memory_size() * 3 < history.len() * whole);
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let same = |a: &Game, b: &Game| {
+        assert_eq!(a.state, b.state);
+        assert_eq!(a.shared.board, b.shared.board);
+        assert_eq!(a.shared.board_visibility, b.shared.board_visibility);
+        assert_eq!(a.shared.points, b.shared.points);
+        assert_eq!(a.shared.turn, b.shared.turn);
+        assert_eq!(a.shared.seats, b.shared.seats);
+        assert!(a.shared.board_history.iter().map(|h| h.board).eq(b
+            .shared
+            .board_history
+            .iter()
+            .map(|h| h.board)));
+    };
+
+    let traitor = GameModifier {
+        traitor: Some(TraitorGo { traitor_count: 10 }),
+        ..GameModifier::default()
+    };
+    let phantom = GameModifier {
+        phantom: Some(PhantomGo {}),
+        ..GameModifier::default()
+    };
+    for mods in vec![GameModifier::default(), traitor, phantom] {
+        let komis = GroupVec::from(&[0, 15][..]);
+        let mut game = Game::standard(&[1, 2], komis, (9, 9), mods, 7).unwrap();
+        game.take_seat(100, 0).unwrap();
+        game.take_seat(200, 1).unwrap();
+
+        let moves = (0..40)
+            .map(|i| (i * 17 % 81 % 9, i * 17 % 81 / 9))
+            .collect::<Vec<_>>();
+        let play = |game: &mut Game, moves: &[(u32, u32)]| {
+            for &(x, y) in moves {
+                let player = [100, 200][game.shared.turn];
+                let _ = game.make_action(player, ActionKind::Place(x, y), Millisecond(0));
+            }
+        };
+        play(&mut game, &moves[..30]);
+
+        let loaded_dump = game.dump();
+        let snapshot = game.snapshot();
+        let mut loaded = Game::load_snapshot(&loaded_dump, &snapshot)
+            .unwrap()
+            .unwrap();
+        let loaded_actions = loaded.actions.len();
+        same(&game, &loaded);
+        same(&Game::load(&loaded_dump).unwrap(), &loaded);
+
+        // The snapshot plays on like the game, traitor stones included
+        play(&mut game, &moves[30..]);
+        play(&mut loaded, &moves[30..]);
+        same(&game, &loaded);
+
+        // Older snapshots play the actions after them
+        let dump = game.dump();
+        assert!(game.actions.len() > loaded_actions);
+        let replayed = Game::load_snapshot(&dump, &snapshot).unwrap().unwrap();
+        same(&game, &replayed);
+
+        // Newer ones aren't used
+        assert!(Game::load_snapshot(&loaded_dump, &game.snapshot())
+            .unwrap()
+            .is_none());
+        let replayed = Game::load_with_snapshot(&loaded_dump, Some(&game.snapshot())).unwrap();
+        assert_eq!(replayed.actions.len(), loaded_actions);
+    }
+}
+
+#[test]
+fn room_snapshots_are_used() {
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+
+    // The room stores the replay after every action and a snapshot every few
+    let mut stored = None;
+    for i in 0..70 {
+        let player = [100, 200][game.shared.turn];
+        let (x, y) = (i * 17 % 81 % 9, i * 17 % 81 / 9);
+        if game
+            .make_action(player, ActionKind::Place(x, y), Millisecond(0))
+            .is_err()
+        {
+            continue;
+        }
+        if game.actions.len() % snapshot::SNAPSHOT_INTERVAL == 0 {
+            stored = Some(game.snapshot());
+        }
+        let dump = game.dump();
+
+        if let Some(snapshot) = &stored {
+            let loaded = Game::load_snapshot(&dump, snapshot).unwrap().unwrap();
+            assert_eq!(loaded.state, game.state);
+            assert_eq!(loaded.shared.board, game.shared.board);
+            assert_eq!(loaded.actions.len(), game.actions.len());
+        }
+    }
+    assert!(stored.is_some());
+}
+
+#[test]
+fn corrupt_snapshots() {
+    use serde_cbor::Value;
+
+    let komis = GroupVec::from(&[0, 15][..]);
+    let mut game = Game::standard(&[1, 2], komis, (9, 9), GameModifier::default(), 0).unwrap();
+    game.take_seat(100, 0).unwrap();
+    game.take_seat(200, 1).unwrap();
+    for i in 0..40 {
+        let player = [100, 200][game.shared.turn];
+        let (x, y) = (i * 17 % 81 % 9, i * 17 % 81 / 9);
+        let _ = game.make_action(player, ActionKind::Place(x, y), Millisecond(0));
+    }
+    let dump = game.dump();
+
+    fn field<'a>(value: &'a mut Value, name: &str) -> &'a mut Value {
+        match value {
+            Value::Map(map) => map.get_mut(&Value::Text(name.to_string())).unwrap(),
+            _ => panic!("not a struct"),
+        }
+    }
+    fn entries(snapshot: &mut Value) -> &mut Vec<Value> {
+        match field(snapshot, "board_history") {
+            Value::Array(entries) => entries,
+            _ => panic!("history is not a list"),
+        }
+    }
+    let corrupt = |change: &dyn Fn(&mut Value)| {
+        let mut snapshot: Value = serde_cbor::from_slice(&game.snapshot()).unwrap();
+        change(&mut snapshot);
+        let snapshot = serde_cbor::to_vec(&snapshot).unwrap();
+
+        assert!(matches!(
+            Game::load_snapshot(&dump, &snapshot),
+            Err(LoadError::Decode(_))
+        ));
+        // The replay is still there
+        let loaded = Game::load_with_snapshot(&dump, Some(&snapshot)).unwrap();
+        assert_eq!(loaded.shared.board, game.shared.board);
+    };
+    // A change outside of the board
+    corrupt(
+        &|snapshot| match field(&mut entries(snapshot)[5], "board") {
+            Value::Map(frame) => {
+                let diff = Value::Array(vec![Value::Array(vec![
+                    Value::Integer(81),
+                    Value::Integer(1),
+                ])]);
+                frame.insert(Value::Text("Diff".to_string()), diff);
+            }
+            _ => panic!("not a frame"),
+        },
+    );
+    // A board without enough points
+    corrupt(&|snapshot| {
+        let full = field(field(&mut entries(snapshot)[0], "board"), "Full");
+        *field(full, "width") = Value::Integer(10);
+    });
+    // Changes without a keyframe before them
+    corrupt(&|snapshot| {
+        entries(snapshot).remove(0);
+    });
+    // Too many moves since the last keyframe
+    corrupt(&|snapshot| {
+        entries(snapshot).remove(history::KEYFRAME_INTERVAL);
+    });
+    // A turn for a seat that isn't there
+    corrupt(&|snapshot| *field(snapshot, "turn") = Value::Integer(2));
+    // Passes of too few seats
+    corrupt(&|snapshot| {
+        let play = field(field(snapshot, "state"), "Play");
+        *field(play, "players_passed") = Value::Array(vec![Value::Bool(false)]);
+    });
+    // A board hash that doesn't match the stones
+    corrupt(&|snapshot| *field(field(snapshot, "board"), "hash") = Value::Integer(1));
+}
@@ -19,6 +19,8 @@ pub enum GameAction {
     LeaveSeat(u32),
     KickPlayer(u64),
     RequestSGF,
+    PlaceShape(u32, u32, game::ShapeChoice),
+    ProposeDraw,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -60,7 +62,7 @@ pub enum ClientMessage {
         room_id: Option<u32>,
         action: GameAction,
     },
-    StartGame(StartGame),
+    StartGame(Box<StartGame>),
     Admin(AdminAction),
     Mode(ClientMode),
 }
@@ -100,6 +102,13 @@ pub enum Error {
     },
     RateLimit,
     Other(Cow<'static, str>),
+    /// The game can't be created with these rules
+    Rules(Vec<game::SetupError>),
+    /// A stored game couldn't be played back
+    GameLoad {
+        room_id: u32,
+        error: game::LoadError,
+    },
 }
 
 impl Error {
@@ -139,6 +148,9 @@ pub enum ServerMessage {
         points: Vec<i32>,
         move_number: u32,
         clock: Option<game::clock::GameClock>,
+        kings: Vec<Option<(u32, u32)>>,
+        seed_commitment: String,
+        resign_votes: Vec<bool>,
     },
     BoardAt {
         room_id: u32,
@@ -1,7 +1,8 @@
 use crate::game::{
-    ActionChange, ActionKind, Board, BoardHistory, Color, GameState, MakeActionError,
-    MakeActionResult, Seat, SharedState, VisibilityBoard,
+    ActionChange, ActionKind, Board, BoardHistory, Color, GameState, GroupVec, History,
+    MakeActionError, MakeActionResult, Point, Seat, ShapeChoice, SharedState, VisibilityBoard,
 };
+use crate::states::play::{king, shape_points};
 use serde::{Deserialize, Serialize};
 
 use itertools::izip;
@@ -13,6 +14,9 @@ pub struct FreePlacement {
     pub stones_placed: Vec<u32>,
     pub players_ready: Vec<bool>,
     pub teams_share_stones: bool,
+    /// Stones of each board in the order they were placed
+    #[serde(default)]
+    pub placed: Vec<Vec<Point>>,
 }
 
 impl FreePlacement {
@@ -32,6 +36,7 @@ impl FreePlacement {
             stones_placed: vec![0; count],
             players_ready: vec![false; seat_count],
             teams_share_stones,
+            placed: vec![Vec::new(); count],
         }
     }
 
@@ -40,67 +45,60 @@ impl FreePlacement {
         shared: &mut SharedState,
         player_id: u64,
         (x, y): (u32, u32),
+        choice: ShapeChoice,
     ) -> MakeActionResult {
         let (seat_idx, active_seat) = get_seat(&shared.seats, player_id);
         let team = active_seat.team;
 
-        let board = if self.teams_share_stones {
-            &mut self.boards[team.0 as usize - 1]
+        let board_idx = if self.teams_share_stones {
+            team.0 as usize - 1
         } else {
-            &mut self.boards[seat_idx]
-        };
-        let stones_placed = if self.teams_share_stones {
-            &mut self.stones_placed[team.0 as usize - 1]
-        } else {
-            &mut self.stones_placed[seat_idx]
+            seat_idx
         };
+        let board = &mut self.boards[board_idx];
+        let stones_placed = &mut self.stones_placed[board_idx];
+        let mut placed = GroupVec::new();
 
         if *stones_placed >= shared.mods.hidden_move.as_ref().unwrap().placement_count {
             return Err(MakeActionError::PointOccupied);
         }
 
-        if shared.mods.pixel {
-            // In pixel mode coordinate 0,0 is outside the board.
-            // This is to adjust for it.
-
-            if x > board.width || y > board.height {
-                return Err(MakeActionError::OutOfBounds);
-            }
-            let x = x as i32 - 1;
-            let y = y as i32 - 1;
+        if let Some(rule) = shared.mods.shape_rule() {
+            let coords = shape_points(board, &rule, (x, y), choice)?;
 
             let mut any_placed = false;
-            for &(x, y) in &[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
-                let coord = match shared.board.wrap_point(x, y) {
-                    Some(x) => x,
-                    None => continue,
-                };
-
-                let point = board.point_mut(coord);
-                if !point.is_empty() {
+            for coord in coords {
+                if !board.get_point(coord).is_empty() {
                     continue;
                 }
-                *point = active_seat.team;
+                board.set_point(coord, active_seat.team);
+                placed.push(coord);
                 any_placed = true;
             }
             if !any_placed {
                 return Err(MakeActionError::PointOccupied);
             }
         } else {
+            if choice != ShapeChoice::default() {
+                return Err(MakeActionError::Illegal);
+            }
             if !board.point_within((x, y)) {
                 return Err(MakeActionError::OutOfBounds);
             }
 
             // TODO: don't repeat yourself
-            let point = board.point_mut((x, y));
-            if !point.is_empty() {
+            if !board.get_point((x, y)).is_empty() {
                 return Err(MakeActionError::PointOccupied);
             }
 
-            *point = active_seat.team;
+            board.set_point((x, y), active_seat.team);
+            placed.push((x, y));
         }
 
         *stones_placed += 1;
+        if let Some(order) = self.placed.get_mut(board_idx) {
+            order.extend(placed);
+        }
 
         Ok(ActionChange::None)
     }
@@ -119,9 +117,13 @@ impl FreePlacement {
             shared.board = board;
             shared.board_visibility = Some(visibility);
 
+            if shared.mods.king.is_some() {
+                self.crown_kings(shared);
+            }
+
             let state = GameState::play(shared.seats.len());
 
-            shared.board_history = vec![BoardHistory {
+            shared.board_history = History::new(BoardHistory {
                 hash: shared.board.hash(),
                 board: shared.board.clone(),
                 board_visibility: shared.board_visibility.clone(),
@@ -129,7 +131,10 @@ impl FreePlacement {
                 points: shared.points.clone(),
                 turn: 0,
                 traitor: shared.traitor.clone(),
-            }];
+                eliminated: shared.eliminated.clone(),
+                kings: shared.kings.clone(),
+                resign_votes: shared.resign_votes.clone(),
+            });
 
             return Ok(ActionChange::SwapState(state));
         }
@@ -137,15 +142,32 @@ impl FreePlacement {
         Ok(ActionChange::None)
     }
 
+    /// Crowns the first stone of each team that made it to the board.
+    fn crown_kings(&self, shared: &mut SharedState) {
+        for team in 1..=shared.komis.len() as u8 {
+            let team = Color(team);
+            let placed = self
+                .placed
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| {
+                    if self.teams_share_stones {
+                        idx + 1 == team.as_usize()
+                    } else {
+                        shared.seats[*idx].team == team
+                    }
+                })
+                .flat_map(|(_, placed)| placed.iter().copied())
+                .collect::<Vec<_>>();
+            king::crown(shared, &placed, team);
+        }
+    }
+
     fn build_board(&self, mut board: Board) -> (Board, VisibilityBoard) {
         let mut visibility = VisibilityBoard::empty(board.width, board.height, board.toroidal);
 
         for view_board in &self.boards {
-            for (a, b, v) in izip!(
-                &mut board.points,
-                &view_board.points,
-                &mut visibility.points
-            ) {
+            for (idx, (b, v)) in izip!(&view_board.points, &mut visibility.points).enumerate() {
                 if *b == Color::empty() {
                     continue;
                 }
@@ -153,10 +175,11 @@ impl FreePlacement {
                 v.set(b.as_usize(), true);
 
                 // Double-committed points become empty!
+                let point = board.idx_to_coord(idx).unwrap();
                 if v.len() == 1 {
-                    *a = *b;
+                    board.set_point(point, *b);
                 } else {
-                    *a = Color::empty();
+                    board.set_point(point, Color::empty());
                 }
             }
         }
@@ -172,20 +195,18 @@ impl FreePlacement {
         let (seat_idx, active_seat) = get_seat(&shared.seats, player_id);
         let team = active_seat.team;
 
-        let board = if self.teams_share_stones {
-            &mut self.boards[team.0 as usize - 1]
-        } else {
-            &mut self.boards[seat_idx]
-        };
-        let stones_placed = if self.teams_share_stones {
-            &mut self.stones_placed[team.0 as usize - 1]
+        let board_idx = if self.teams_share_stones {
+            team.0 as usize - 1
         } else {
-            &mut self.stones_placed[seat_idx]
+            seat_idx
         };
 
         self.players_ready[seat_idx] = false;
-        *board = shared.board.clone();
-        *stones_placed = 0;
+        self.boards[board_idx] = shared.board.clone();
+        self.stones_placed[board_idx] = 0;
+        if let Some(order) = self.placed.get_mut(board_idx) {
+            order.clear();
+        }
 
         Ok(ActionChange::None)
     }
@@ -197,13 +218,19 @@ impl FreePlacement {
         action: ActionKind,
     ) -> MakeActionResult {
         match action {
-            ActionKind::Place(x, y) => self.make_action_place(shared, player_id, (x, y)),
+            ActionKind::Place(x, y) => {
+                self.make_action_place(shared, player_id, (x, y), ShapeChoice::default())
+            }
+            ActionKind::PlaceShape(x, y, choice) => {
+                self.make_action_place(shared, player_id, (x, y), choice)
+            }
             ActionKind::Pass => self.make_action_pass(shared, player_id),
             ActionKind::Cancel => self.make_action_cancel(shared, player_id),
             ActionKind::Resign => {
                 // We don't allow resigning in free placement
                 Ok(ActionChange::None)
             }
+            ActionKind::ProposeDraw => Err(MakeActionError::Illegal),
         }
     }
 }
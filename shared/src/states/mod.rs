@@ -1,5 +1,6 @@
 pub mod free_placement;
 pub mod play;
+pub(crate) mod rules;
 pub mod scoring;
 
 pub use self::free_placement::FreePlacement;
@@ -7,7 +8,7 @@ pub use self::play::PlayState;
 pub use self::scoring::ScoringState;
 
 use crate::assume::AssumeFrom;
-use crate::game::{Board, Seat};
+use crate::game::{Board, SharedState};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -37,8 +38,8 @@ impl GameState {
         GameState::Play(PlayState::new(seat_count))
     }
 
-    pub fn scoring(board: &Board, seats: &[Seat], scores: &[i32]) -> Self {
-        GameState::Scoring(ScoringState::new(board, seats, scores))
+    pub fn scoring(shared: &SharedState) -> Self {
+        GameState::Scoring(ScoringState::new(shared))
     }
 }
 
@@ -1,24 +1,34 @@
+mod atari;
+pub(crate) mod king;
+mod last_survivor;
+mod move_limit;
 mod n_plus_one;
+mod phantom;
+pub(crate) mod points;
+pub(crate) mod starting_stones;
 mod tetris;
 pub(crate) mod traitor;
+mod zen;
 
 use crate::game::{
-    find_groups, ActionChange, ActionKind, Board, BoardHistory, Color, GameState, Group, GroupVec,
-    MakeActionError, MakeActionResult, Point, SharedState, VisibilityBoard,
+    ActionChange, ActionKind, Board, BoardHistory, Color, GameState, Group, GroupVec,
+    MakeActionError, MakeActionResult, Point, ShapeChoice, ShapeGo, SharedState, VisibilityBoard,
 };
 use serde::{Deserialize, Serialize};
 
 use bitmaps::Bitmap;
 use tinyvec::tiny_vec;
 
+use super::rules::{self, Revealed, Rule, TurnEnd};
 use super::ScoringState;
 
-type Revealed = bool;
-
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlayState {
     // TODO: use smallvec?
     pub players_passed: Vec<bool>,
+    /// Seats that have offered a draw since the last stone was placed
+    #[serde(default)]
+    pub draw_proposed: Vec<bool>,
     pub last_stone: Option<GroupVec<(u32, u32)>>,
     /// Optimization for superko
     pub capture_count: usize,
@@ -28,6 +38,7 @@ impl PlayState {
     pub fn new(seat_count: usize) -> Self {
         PlayState {
             players_passed: vec![false; seat_count],
+            draw_proposed: vec![false; seat_count],
             last_stone: None,
             capture_count: 0,
         }
@@ -38,28 +49,16 @@ impl PlayState {
         shared: &mut SharedState,
         (x, y): Point,
         color_placed: Color,
+        choice: ShapeChoice,
     ) -> MakeActionResult<GroupVec<Point>> {
         let mut points_played = GroupVec::new();
 
-        if shared.mods.pixel {
-            // In pixel mode coordinate 0,0 is outside the board.
-            // This is to adjust for it.
-
-            if x > shared.board.width || y > shared.board.height {
-                return Err(MakeActionError::OutOfBounds);
-            }
-            let x = x as i32 - 1;
-            let y = y as i32 - 1;
+        if let Some(rule) = shared.mods.shape_rule() {
+            let coords = shape_points(&shared.board, &rule, (x, y), choice)?;
 
             let mut any_placed = false;
             let mut any_revealed = false;
-            for &(x, y) in &[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)] {
-                let coord = match shared.board.wrap_point(x, y) {
-                    Some(x) => x,
-                    None => continue,
-                };
-
-                let point = shared.board.point_mut(coord);
+            for coord in coords {
                 if let Some(visibility) = &mut shared.board_visibility {
                     if !visibility.get_point(coord).is_empty() {
                         any_revealed = true;
@@ -67,10 +66,10 @@ impl PlayState {
                     }
                     *visibility.point_mut(coord) = Bitmap::new();
                 }
-                if !point.is_empty() {
+                if !shared.board.get_point(coord).is_empty() {
                     continue;
                 }
-                *point = color_placed;
+                shared.board.set_point(coord, color_placed);
                 points_played.push(coord);
                 any_placed = true;
             }
@@ -82,12 +81,14 @@ impl PlayState {
                 return Err(MakeActionError::PointOccupied);
             }
         } else {
+            if choice != ShapeChoice::default() {
+                return Err(MakeActionError::Illegal);
+            }
             if !shared.board.point_within((x, y)) {
                 return Err(MakeActionError::OutOfBounds);
             }
 
             // TODO: don't repeat yourself
-            let point = shared.board.point_mut((x, y));
             let revealed = if let Some(visibility) = &mut shared.board_visibility {
                 let revealed = !visibility.get_point((x, y)).is_empty();
                 *visibility.point_mut((x, y)) = Bitmap::new();
@@ -95,7 +96,7 @@ impl PlayState {
             } else {
                 false
             };
-            if !point.is_empty() {
+            if !shared.board.get_point((x, y)).is_empty() {
                 if revealed {
                     self.last_stone = Some(tiny_vec![[Point; 8] => (x, y)]);
                     return Ok(points_played);
@@ -103,7 +104,7 @@ impl PlayState {
                 return Err(MakeActionError::PointOccupied);
             }
 
-            *point = color_placed;
+            shared.board.set_point((x, y), color_placed);
             points_played.push((x, y));
         }
 
@@ -113,6 +114,7 @@ impl PlayState {
     fn capture(
         &self,
         shared: &mut SharedState,
+        rules: &[Box<dyn Rule>],
         points_played: &mut GroupVec<Point>,
         color_placed: Color,
     ) -> (usize, Revealed) {
@@ -120,46 +122,37 @@ impl PlayState {
         let mut captures = 0;
         let mut revealed = false;
 
-        if shared.mods.phantom.is_some() {
-            let groups = find_groups(&shared.board);
-            // TODO: PUZZLE add a concept for pixel go atari
-            let ataris = groups.iter().filter(|g| g.liberties == 1);
-            for group in ataris {
-                let reveals = reveal_group(shared.board_visibility.as_mut(), group, &shared.board);
-                revealed = revealed || reveals;
-            }
-        }
+        let alliances = shared.mods.alliances.clone();
+        let friendly = |a: Color, b: Color| match &alliances {
+            Some(alliances) => alliances.allied(a, b),
+            None => a == b,
+        };
 
         let mut kill = |shared: &mut SharedState, group: &Group| -> Revealed {
             let board = &mut shared.board;
             for point in &group.points {
-                *board.point_mut(*point) = Color::empty();
+                board.set_point(*point, Color::empty());
                 captures += 1;
             }
             let reveals = reveal_group(shared.board_visibility.as_mut(), group, board);
 
-            if let Some(ponnuki) = shared.mods.ponnuki_is_points {
-                let surrounding_count = board.surrounding_points(group.points[0]).count();
-                if group.points.len() == 1
-                    && surrounding_count == 4
-                    && board
-                        .surrounding_points(group.points[0])
-                        .all(|p| board.get_point(p) == color_placed)
-                    && board
-                        .surrounding_diagonal_points(group.points[0])
-                        .all(|p| board.get_point(p) != color_placed)
-                {
-                    shared.points[color_placed.0 as usize - 1] += ponnuki;
-                }
+            for rule in rules {
+                rule.after_capture(shared, group, color_placed);
             }
 
             reveals
         };
 
-        let groups = find_groups(&shared.board);
-        let dead_opponents = groups
-            .iter()
-            .filter(|g| g.liberties == 0 && g.team != color_placed);
+        let neutral_capturable = shared
+            .mods
+            .neutral_stones
+            .as_ref()
+            .is_some_and(|n| n.capturable);
+
+        let groups = shared.groups.dead_groups(&shared.board, alliances.as_ref());
+        let dead_opponents = groups.iter().filter(|g| {
+            !friendly(g.team, color_placed) && (!g.team.is_neutral() || neutral_capturable)
+        });
 
         for group in dead_opponents {
             // Don't forget about short-circuiting boolean operators...
@@ -168,17 +161,15 @@ impl PlayState {
         }
 
         // TODO: only re-scan own previously dead grouos
-        let groups = find_groups(&shared.board);
-        let dead_own = groups
-            .iter()
-            .filter(|g| g.liberties == 0 && g.team == color_placed);
+        let groups = shared.groups.dead_groups(&shared.board, alliances.as_ref());
+        let dead_own = groups.iter().filter(|g| friendly(g.team, color_placed));
 
         for group in dead_own {
             let mut removed_move = false;
             for point in &group.points {
                 if points_played.contains(point) && color_placed == active_seat.team {
                     points_played.retain(|x| x != point);
-                    *shared.board.point_mut(*point) = Color::empty();
+                    shared.board.set_point(*point, Color::empty());
                     removed_move = true;
                 }
             }
@@ -193,46 +184,27 @@ impl PlayState {
             }
         }
 
-        if shared.mods.captures_give_points.is_some() {
-            shared.points[active_seat.team.0 as usize - 1] += captures as i32 * 2;
-        }
-
         (captures, revealed)
     }
 
     /// Superko
     /// We only need to scan back capture_count boards, as per Ten 1p's clever idea.
     /// The board can't possibly repeat further back than the number of removed stones.
-    fn superko(
-        &self,
-        shared: &mut SharedState,
-        captures: usize,
-        hash: u64,
-    ) -> MakeActionResult<()> {
-        for BoardHistory {
-            hash: old_hash,
-            board: old_board,
-            ..
-        } in shared
-            .board_history
-            .iter()
-            .rev()
-            .take(self.capture_count + captures)
-        {
-            if *old_hash == hash && old_board == &shared.board {
-                let BoardHistory {
-                    board: old_board,
-                    points: old_points,
-                    ..
-                } = shared
-                    .board_history
-                    .last()
-                    .expect("board_history.last() shouldn't be None")
-                    .clone();
-                shared.board = old_board;
-                shared.points = old_points;
-                return Err(MakeActionError::Ko);
-            }
+    fn superko(&self, shared: &mut SharedState, captures: usize) -> MakeActionResult<()> {
+        let depth = self.capture_count + captures;
+        if shared.board_history.repeats(&shared.board, depth) {
+            let BoardHistory {
+                board: old_board,
+                points: old_points,
+                ..
+            } = shared
+                .board_history
+                .last()
+                .expect("board_history.last() shouldn't be None")
+                .clone();
+            shared.board = old_board;
+            shared.points = old_points;
+            return Err(MakeActionError::Ko);
         }
 
         Ok(())
@@ -241,47 +213,25 @@ impl PlayState {
     fn make_action_place(
         &mut self,
         shared: &mut SharedState,
+        rules: &[Box<dyn Rule>],
         (x, y): (u32, u32),
         color_placed: Color,
+        choice: ShapeChoice,
     ) -> MakeActionResult {
         // TODO: should use some kind of set to make suicide prevention faster
-        let mut points_played = self.place_stone(shared, (x, y), color_placed)?;
+        let mut points_played = self.place_stone(shared, (x, y), color_placed, choice)?;
         if points_played.is_empty() {
             return Ok(ActionChange::None);
         }
 
-        if let Some(rule) = &shared.mods.tetris {
-            // This is valid because points_played is empty if the move is illegal.
-            use tetris::TetrisResult::*;
-            match tetris::check(&mut points_played, &mut shared.board, rule) {
-                Nothing => {}
-                Illegal => {
-                    return Err(MakeActionError::Illegal);
-                }
-            }
-        }
-
-        if shared.mods.phantom.is_some() {
-            let seat = shared.get_active_seat();
-            let visibility = shared
-                .board_visibility
-                .as_mut()
-                .expect("Visibility board not initialized with phantom go");
-            for &point in &points_played {
-                // The hidden layer can't deal with being able to see someone else's stones, so if we played
-                // a stone of wrong color (eg. a traitor), just reveal it.
-                if shared.board.get_point(point) != seat.team {
-                    continue;
-                }
-
-                let mut v = Bitmap::new();
-                v.set(seat.team.as_usize(), true);
-
-                *visibility.point_mut(point) = v;
-            }
+        let mut revealed = false;
+        for rule in rules {
+            let reveals = rule.before_place(shared, &mut points_played)?;
+            revealed = revealed || reveals;
         }
 
-        let (captures, revealed) = self.capture(shared, &mut points_played, color_placed);
+        let (captures, reveals) = self.capture(shared, rules, &mut points_played, color_placed);
+        let revealed = revealed || reveals;
 
         if points_played.is_empty() {
             let BoardHistory { board, points, .. } = shared
@@ -298,39 +248,42 @@ impl PlayState {
             return Err(MakeActionError::Suicide);
         }
 
-        let hash = shared.board.hash();
+        self.superko(shared, captures)?;
 
-        self.superko(shared, captures, hash)?;
-
-        let new_turn = if let Some(rule) = &shared.mods.n_plus_one {
-            use n_plus_one::NPlusOneResult::*;
-            match n_plus_one::check(
-                &points_played,
-                &shared.board,
-                shared.board_visibility.as_mut(),
-                rule,
-            ) {
-                ExtraTurn => true,
-                Nothing => false,
-            }
-        } else {
-            false
-        };
+        let mut turn_end = TurnEnd::default();
+        for rule in rules {
+            let res = rule.on_turn_end(shared, &points_played);
+            turn_end.extra_turn = turn_end.extra_turn || res.extra_turn;
+            turn_end.game_over = turn_end.game_over || res.game_over;
+            turn_end.scoring = turn_end.scoring || res.scoring;
+        }
 
+        // Hidden stones are filtered out of the marker per viewer, see `GameStateView::new`.
         self.last_stone = Some(points_played);
 
-        // TODO: Handle this at the view layer instead to have the marker visible for your own stones.
-        if shared.mods.phantom.is_some() {
-            self.last_stone = None;
-        }
-
         for passed in &mut self.players_passed {
             *passed = false;
         }
+        for proposed in &mut self.draw_proposed {
+            *proposed = false;
+        }
+
+        // The player can't keep their extra turn if their team was knocked out.
+        let new_turn = turn_end.extra_turn && !shared.get_active_seat().resigned;
 
         self.next_turn(shared, new_turn);
         self.capture_count += captures;
 
+        if turn_end.game_over {
+            let mut scoring = ScoringState::new(shared);
+            scoring.lost = shared.eliminated.clone();
+            return Ok(ActionChange::PushState(GameState::Done(scoring)));
+        }
+
+        if turn_end.scoring {
+            return Ok(ActionChange::PushState(GameState::scoring(shared)));
+        }
+
         Ok(ActionChange::None)
     }
 
@@ -345,20 +298,22 @@ impl PlayState {
 
         self.next_turn(shared, false);
 
-        if shared
-            .seats
-            .iter()
-            .zip(&self.players_passed)
-            .all(|(s, &pass)| s.resigned || pass)
+        let out_of_moves = match &shared.mods.move_limit {
+            Some(rule) => move_limit::reached(shared, rule),
+            None => false,
+        };
+
+        if out_of_moves
+            || shared
+                .seats
+                .iter()
+                .zip(&self.players_passed)
+                .all(|(s, &pass)| s.resigned || pass)
         {
             for passed in &mut self.players_passed {
                 *passed = false;
             }
-            return Ok(ActionChange::PushState(GameState::scoring(
-                &shared.board,
-                &shared.seats,
-                &shared.points,
-            )));
+            return Ok(ActionChange::PushState(GameState::scoring(shared)));
         }
 
         Ok(ActionChange::None)
@@ -374,7 +329,23 @@ impl PlayState {
             return Err(MakeActionError::Illegal);
         }
 
-        self.rollback_turn(shared, true)
+        self.rollback_turn(shared, true)?;
+
+        // Votes cast since are undone too, but a team that already resigned stays out.
+        // Only here, as the other rollbacks replay a move of the same turn.
+        let history = shared.board_history.last().unwrap();
+        for ((vote, seat), &before) in shared
+            .resign_votes
+            .iter_mut()
+            .zip(&shared.seats)
+            .zip(&history.resign_votes)
+        {
+            if !seat.resigned {
+                *vote = before;
+            }
+        }
+
+        Ok(ActionChange::None)
     }
 
     fn rollback_turn(
@@ -386,6 +357,15 @@ impl PlayState {
             .board_history
             .pop()
             .ok_or(MakeActionError::OutOfBounds)?;
+        self.restore_turn(shared, roll_visibility)
+    }
+
+    /// Goes back to the state of the last move in history.
+    fn restore_turn(
+        &mut self,
+        shared: &mut SharedState,
+        roll_visibility: bool,
+    ) -> MakeActionResult {
         let history = shared
             .board_history
             .last()
@@ -398,28 +378,47 @@ impl PlayState {
         shared.points = history.points.clone();
         shared.turn = history.turn;
         shared.traitor = history.traitor.clone();
+        shared.kings = history.kings.clone();
+        let eliminated = history.eliminated.clone();
 
         *self = history.state.assume::<PlayState>().clone();
 
+        last_survivor::restore(shared, eliminated);
+
         Ok(ActionChange::None)
     }
 
-    fn make_action_resign(&mut self, shared: &mut SharedState) -> MakeActionResult {
-        let active_seat = shared
-            .seats
-            .get_mut(shared.turn)
-            .expect("Game turn number invalid");
-
-        active_seat.resigned = true;
+    fn make_action_resign(&mut self, shared: &mut SharedState, player_id: u64) -> MakeActionResult {
+        let game_over = if shared.mods.team_resign.is_some() {
+            // A single player can hold multiple seats so every seat they hold votes
+            let seats = shared
+                .seats
+                .iter()
+                .enumerate()
+                .filter(|(_, seat)| seat.player == Some(player_id))
+                .map(|(idx, _)| idx)
+                .collect::<GroupVec<_>>();
+            shared.resign_seats(&seats);
+            shared.one_team_left()
+        } else {
+            if shared.get_active_seat().player != Some(player_id) {
+                return Err(MakeActionError::NotTurn);
+            }
+            shared.resign_seats(&[shared.turn]);
+            shared.seats.iter().filter(|s| !s.resigned).count() <= 1
+        };
 
-        if shared.seats.iter().filter(|s| !s.resigned).count() <= 1 {
+        if game_over {
             return Ok(ActionChange::PushState(GameState::Done(ScoringState::new(
-                &shared.board,
-                &shared.seats,
-                &shared.points,
+                shared,
             ))));
         }
 
+        // The vote alone doesn't end the turn
+        if !shared.get_active_seat().resigned {
+            return Ok(ActionChange::None);
+        }
+
         loop {
             shared.turn += 1;
             if shared.turn >= shared.seats.len() {
@@ -433,60 +432,105 @@ impl PlayState {
         Ok(ActionChange::None)
     }
 
+    /// Marks every seat of the player as wanting a draw. Ends the game once all seats
+    /// still in the game agree.
+    fn make_action_propose_draw(
+        &mut self,
+        shared: &mut SharedState,
+        player_id: u64,
+    ) -> MakeActionResult {
+        let seats = shared
+            .seats
+            .iter()
+            .zip(self.draw_proposed.iter_mut())
+            .filter(|(seat, _)| seat.player == Some(player_id));
+
+        for (_, proposed) in seats {
+            *proposed = true;
+        }
+
+        if shared
+            .seats
+            .iter()
+            .zip(&self.draw_proposed)
+            .all(|(s, &proposed)| s.resigned || proposed)
+        {
+            let mut scoring = ScoringState::new(shared);
+            scoring.draw = true;
+            return Ok(ActionChange::PushState(GameState::Done(scoring)));
+        }
+
+        Ok(ActionChange::None)
+    }
+
     pub fn make_action(
         &mut self,
         shared: &mut SharedState,
         player_id: u64,
         action: ActionKind,
     ) -> MakeActionResult {
+        if action == ActionKind::ProposeDraw {
+            return self.make_action_propose_draw(shared, player_id);
+        }
+
+        // Team resign votes can be cast on any turn, so resigning checks the turn itself
         let active_seat = shared.get_active_seat();
-        if active_seat.player != Some(player_id) {
+        if action != ActionKind::Resign && active_seat.player != Some(player_id) {
             return Err(MakeActionError::NotTurn);
         }
 
+        let (action, choice) = match action {
+            ActionKind::PlaceShape(x, y, choice) => (ActionKind::Place(x, y), choice),
+            action => (action, ShapeChoice::default()),
+        };
+
+        let rules = rules::rules(&shared.mods);
+
         let res = match action {
             ActionKind::Place(x, y) => {
                 let depth = shared.board_history.len();
 
-                let res = self.make_action_place(shared, (x, y), active_seat.team);
-
-                if res.is_ok() && shared.board_history.len() > depth && shared.traitor.is_some() {
-                    // Depth increased -> the move is legal.
-                    // Replay using traitor stone.
+                let res = self.make_action_place(shared, &rules, (x, y), active_seat.team, choice);
 
-                    let _ = self.rollback_turn(shared, true);
+                let picker = rules.iter().find(|rule| rule.picks_stone_color());
+                match picker {
+                    Some(rule) if res.is_ok() && shared.board_history.len() > depth => {
+                        // Depth increased -> the move is legal.
+                        // Replay using the picked color.
+                        let _ = self.rollback_turn(shared, true);
 
-                    let traitor = shared.traitor.clone();
-                    let color_placed = if let Some(state) = &mut shared.traitor {
-                        state.next_color(active_seat.team)
-                    } else {
-                        unreachable!();
-                    };
+                        let color_placed = rule.stone_color(shared, active_seat.team);
+                        let res =
+                            self.make_action_place(shared, &rules, (x, y), color_placed, choice);
 
-                    let res = self.make_action_place(shared, (x, y), color_placed);
+                        if res.is_err() {
+                            // Forget the picked color
+                            let _ = self.restore_turn(shared, false);
+                        }
+                        res
+                    }
+                    Some(_) if res.is_ok() => {
+                        // Replays roll visibility back, so store visibility changes to history.
+                        let mut history = shared.board_history.pop().unwrap();
+                        history.board_visibility = shared.board_visibility.clone();
+                        shared.board_history.push(history);
 
-                    if res.is_err() {
-                        shared.traitor = traitor;
+                        res
                     }
-                    res
-                } else if res.is_ok() && shared.traitor.is_some() {
-                    // Store visibility changes to history.
-                    let history = shared.board_history.last_mut().unwrap();
-                    history.board_visibility = shared.board_visibility.clone();
-
-                    res
-                } else {
-                    res
+                    _ => res,
                 }
             }
             ActionKind::Pass => self.make_action_pass(shared),
             ActionKind::Cancel => self.make_action_cancel(shared),
-            ActionKind::Resign => self.make_action_resign(shared),
+            ActionKind::Resign => self.make_action_resign(shared, player_id),
+            ActionKind::PlaceShape(..) | ActionKind::ProposeDraw => unreachable!(),
         };
 
         let res = res?;
 
-        self.set_zen_teams(shared);
+        for rule in &rules {
+            rule.after_action(shared);
+        }
 
         Ok(res)
     }
@@ -512,17 +556,34 @@ impl PlayState {
             points: shared.points.clone(),
             turn: shared.turn,
             traitor: shared.traitor.clone(),
+            eliminated: shared.eliminated.clone(),
+            kings: shared.kings.clone(),
+            resign_votes: shared.resign_votes.clone(),
         });
     }
+}
 
-    fn set_zen_teams(&mut self, shared: &mut SharedState) {
-        let move_number = shared.board_history.len() - 1;
-        if let Some(zen) = &shared.mods.zen_go {
-            for seat in &mut shared.seats {
-                seat.team = Color((move_number % zen.color_count as usize) as u8 + 1);
-            }
-        }
+/// Board points covered by a shape placed at the given coordinates.
+/// Shared between normal play and hidden move placement.
+pub(crate) fn shape_points<T: Copy + Default>(
+    board: &Board<T>,
+    rule: &ShapeGo,
+    (x, y): Point,
+    choice: ShapeChoice,
+) -> MakeActionResult<GroupVec<Point>> {
+    // Pixel shapes are placed relative to the corners of the points, so the coordinates
+    // go one further than the board.
+    let (width, height) = if rule.is_pixel() {
+        (board.width + 1, board.height + 1)
+    } else {
+        (board.width, board.height)
+    };
+    if x >= width || y >= height {
+        return Err(MakeActionError::OutOfBounds);
     }
+
+    rule.points(board, (x, y), choice)
+        .ok_or(MakeActionError::Illegal)
 }
 
 pub(self) fn reveal_group(
@@ -0,0 +1,72 @@
+use super::shape_points;
+use crate::game::{Board, Group, GroupVec, Point, ShapeChoice, ShapeGo};
+
+/// A group is in atari if it can be captured on the next move.
+/// With single stones that means one liberty, but shapes can fill several liberties at once.
+pub fn in_atari(board: &Board, group: &Group, shape: Option<&ShapeGo>) -> bool {
+    let rule = match shape {
+        Some(rule) => rule,
+        None => return group.liberties == 1,
+    };
+
+    if group.liberties == 0 || group.liberties as usize > rule.max_size() {
+        return group.liberties == 1;
+    }
+
+    let liberties = liberty_points(board, group);
+
+    let rotations = if rule.allow_rotation { 4 } else { 1 };
+    for shape in 0..rule.shapes.len() as u32 {
+        for rotation in 0..rotations {
+            let choice = ShapeChoice { shape, rotation };
+            let offsets = match rule.offsets(choice) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            // Any placement that fills every liberty has to cover the first one,
+            // so only try the placements that do.
+            let (lx, ly) = liberties[0];
+            for &(ox, oy) in &offsets {
+                let anchor = match anchor_point(board, lx as i32 - ox, ly as i32 - oy) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                // Only placements the player could actually make
+                let covered = match shape_points(board, rule, anchor, choice) {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                if liberties.iter().all(|p| covered.contains(p)) {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Anchors past the edge only exist on toroidal boards, where they wrap around.
+fn anchor_point(board: &Board, x: i32, y: i32) -> Option<Point> {
+    if board.toroidal {
+        let (width, height) = (board.width as i32, board.height as i32);
+        Some((x.rem_euclid(width) as u32, y.rem_euclid(height) as u32))
+    } else if x < 0 || y < 0 {
+        None
+    } else {
+        Some((x as u32, y as u32))
+    }
+}
+
+fn liberty_points(board: &Board, group: &Group) -> GroupVec<Point> {
+    let mut liberties = GroupVec::new();
+    for &point in &group.points {
+        for p in board.surrounding_points(point) {
+            if board.get_point(p).is_empty() && !liberties.contains(&p) {
+                liberties.push(p);
+            }
+        }
+    }
+    liberties
+}
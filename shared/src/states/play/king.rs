@@ -0,0 +1,83 @@
+use super::last_survivor;
+use crate::game::{Color, GameStateView, GameView, GroupVec, KingGo, Point, SharedState};
+use crate::states::rules::{Rule, TurnEnd};
+
+pub enum KingResult {
+    GameOver,
+    Nothing,
+}
+
+/// The first stone a team places becomes its king.
+pub fn crown(shared: &mut SharedState, points_played: &[Point], team: Color) {
+    let board = &shared.board;
+    let king = match shared.kings.get_mut(team.as_usize() - 1) {
+        Some(king) => king,
+        None => return,
+    };
+
+    if king.is_none() {
+        *king = points_played
+            .iter()
+            .copied()
+            .find(|&p| board.get_point(p) == team);
+    }
+}
+
+/// Knocks out every team whose king is no longer on the board.
+pub fn check(shared: &mut SharedState) -> KingResult {
+    let alive = last_survivor::alive_teams(shared);
+
+    let captured = shared
+        .kings
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, king)| {
+            let team = Color(idx as u8 + 1);
+            let point = (*king)?;
+            if alive.contains(&team) && shared.board.get_point(point) != team {
+                Some(team)
+            } else {
+                None
+            }
+        })
+        .collect::<GroupVec<Color>>();
+
+    for team in captured {
+        last_survivor::knock_out(shared, team);
+    }
+
+    if last_survivor::alive_teams(shared).len() <= 1 {
+        KingResult::GameOver
+    } else {
+        KingResult::Nothing
+    }
+}
+
+impl Rule for KingGo {
+    fn on_turn_end(&self, shared: &mut SharedState, points_played: &GroupVec<Point>) -> TurnEnd {
+        let team = shared.get_active_seat().team;
+        crown(shared, points_played, team);
+        TurnEnd {
+            game_over: matches!(check(shared), KingResult::GameOver),
+            ..TurnEnd::default()
+        }
+    }
+
+    /// With hidden stones, kings are only visible to their own team.
+    fn on_view(&self, shared: &SharedState, player_id: u64, view: &mut GameView) {
+        let hidden = shared.mods.phantom.is_some() || shared.mods.hidden_move.is_some();
+        let game_done = matches!(view.state, GameStateView::Done(_));
+        let seat = shared.seats.iter().find(|x| x.player == Some(player_id));
+
+        if !hidden || game_done || (seat.is_none() && shared.mods.observable) {
+            return;
+        }
+
+        for (idx, king) in view.kings.iter_mut().enumerate() {
+            match seat {
+                Some(seat) if seat.team.as_usize() == idx + 1 => {}
+                _ => *king = None,
+            }
+        }
+    }
+}
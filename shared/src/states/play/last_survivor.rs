@@ -0,0 +1,92 @@
+use crate::game::{Color, GroupVec, LastSurvivor, Point, SharedState};
+use crate::states::rules::{Rule, TurnEnd};
+
+pub enum LastSurvivorResult {
+    GameOver,
+    Nothing,
+}
+
+/// Knocks out every team that has too few stones left on the board.
+/// The seats of knocked out teams are marked resigned so turn order skips them.
+pub fn check(shared: &mut SharedState, rule: &LastSurvivor) -> LastSurvivorResult {
+    let move_number = shared.board_history.len();
+    if move_number <= rule.grace_moves as usize {
+        return LastSurvivorResult::Nothing;
+    }
+
+    let min_stones = rule.min_stones.max(1) as usize;
+    let team_count = shared.komis.len();
+
+    let mut stone_counts = vec![0usize; team_count + 1];
+    for &color in &shared.board.points {
+        if let Some(count) = stone_counts.get_mut(color.as_usize()) {
+            *count += 1;
+        }
+    }
+
+    let alive = alive_teams(shared);
+
+    let knocked_out = alive
+        .iter()
+        .copied()
+        .filter(|team| stone_counts[team.as_usize()] < min_stones)
+        .collect::<GroupVec<Color>>();
+
+    // Never knock out everyone at once; the game has to have a winner.
+    if knocked_out.len() < alive.len() {
+        for &team in &knocked_out {
+            knock_out(shared, team);
+        }
+    }
+
+    if alive_teams(shared).len() <= 1 {
+        LastSurvivorResult::GameOver
+    } else {
+        LastSurvivorResult::Nothing
+    }
+}
+
+impl Rule for LastSurvivor {
+    fn on_turn_end(&self, shared: &mut SharedState, _points_played: &GroupVec<Point>) -> TurnEnd {
+        TurnEnd {
+            game_over: matches!(check(shared, self), LastSurvivorResult::GameOver),
+            ..TurnEnd::default()
+        }
+    }
+}
+
+/// Takes a team out of the game by resigning all of its seats.
+pub(super) fn knock_out(shared: &mut SharedState, team: Color) {
+    shared.eliminated.push(team);
+    for seat in &mut shared.seats {
+        if seat.team == team {
+            seat.resigned = true;
+        }
+    }
+}
+
+/// Restores the seats of teams that are no longer knocked out after an undo.
+pub fn restore(shared: &mut SharedState, eliminated: GroupVec<Color>) {
+    for team in &shared.eliminated {
+        if eliminated.contains(team) {
+            continue;
+        }
+        for seat in &mut shared.seats {
+            if seat.team == *team {
+                seat.resigned = false;
+            }
+        }
+    }
+    shared.eliminated = eliminated;
+}
+
+/// Teams that still have a seat in play.
+pub(super) fn alive_teams(shared: &SharedState) -> GroupVec<Color> {
+    let mut teams = GroupVec::new();
+    for seat in &shared.seats {
+        if !seat.resigned && !teams.contains(&seat.team) {
+            teams.push(seat.team);
+        }
+    }
+    teams
+}
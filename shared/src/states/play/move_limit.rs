@@ -0,0 +1,18 @@
+use crate::game::{GroupVec, MoveLimit, Point, SharedState};
+use crate::states::rules::{Rule, TurnEnd};
+
+/// Whether the game has run out of moves. Passes count as moves too.
+pub fn reached(shared: &SharedState, rule: &MoveLimit) -> bool {
+    // The first entry in the history is the starting position.
+    shared.board_history.len() > rule.moves as usize
+}
+
+impl Rule for MoveLimit {
+    fn on_turn_end(&self, shared: &mut SharedState, _points_played: &GroupVec<Point>) -> TurnEnd {
+        // The history entry of this move isn't pushed yet.
+        TurnEnd {
+            scoring: shared.board_history.len() >= self.moves as usize,
+            ..TurnEnd::default()
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::game::{Board, GroupVec, NPlusOne, Point, Visibility, VisibilityBoard};
+use crate::game::{Board, GroupVec, NPlusOne, Point, SharedState, Visibility, VisibilityBoard};
+use crate::states::rules::{Rule, TurnEnd};
 
 pub enum NPlusOneResult {
     ExtraTurn,
@@ -174,3 +175,18 @@ pub fn check(
 
     NPlusOneResult::Nothing
 }
+
+impl Rule for NPlusOne {
+    fn on_turn_end(&self, shared: &mut SharedState, points_played: &GroupVec<Point>) -> TurnEnd {
+        let result = check(
+            points_played,
+            &shared.board,
+            shared.board_visibility.as_mut(),
+            self,
+        );
+        TurnEnd {
+            extra_turn: matches!(result, NPlusOneResult::ExtraTurn),
+            ..TurnEnd::default()
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use bitmaps::Bitmap;
+
+use super::{atari, reveal_group};
+use crate::game::{GroupVec, MakeActionResult, PhantomGo, Point, SharedState};
+use crate::states::rules::{Revealed, Rule};
+
+impl Rule for PhantomGo {
+    /// New stones are only visible to the team that placed them.
+    /// Groups in atari are revealed to everyone.
+    fn before_place(
+        &self,
+        shared: &mut SharedState,
+        points_played: &mut GroupVec<Point>,
+    ) -> MakeActionResult<Revealed> {
+        let seat = shared.get_active_seat();
+        let visibility = shared
+            .board_visibility
+            .as_mut()
+            .expect("Visibility board not initialized with phantom go");
+        for &point in points_played.iter() {
+            // The hidden layer can't deal with being able to see someone else's stones, so if we played
+            // a stone of wrong color (eg. a traitor), just reveal it.
+            if shared.board.get_point(point) != seat.team {
+                continue;
+            }
+
+            let mut v = Bitmap::new();
+            v.set(seat.team.as_usize(), true);
+
+            *visibility.point_mut(point) = v;
+        }
+
+        let mut revealed = false;
+        let groups = shared
+            .groups
+            .groups(&shared.board, shared.mods.alliances.as_ref());
+        // Version 0 revealed only groups with one liberty, even in pixel go
+        let shape = match shared.version {
+            0 => None,
+            _ => shared.mods.shape_rule(),
+        };
+        let ataris = groups
+            .iter()
+            .filter(|g| atari::in_atari(&shared.board, g, shape.as_ref()))
+            .collect::<Vec<_>>();
+        for group in ataris {
+            let reveals = reveal_group(shared.board_visibility.as_mut(), group, &shared.board);
+            revealed = revealed || reveals;
+        }
+
+        Ok(revealed)
+    }
+}
@@ -0,0 +1,30 @@
+use crate::game::{CapturesGivePoints, Color, Group, SharedState};
+use crate::states::rules::Rule;
+
+/// "Ponnuki is 30 points". Capturing a single stone with a ponnuki gives (or takes) points.
+pub struct Ponnuki(pub i32);
+
+impl Rule for Ponnuki {
+    fn after_capture(&self, shared: &mut SharedState, group: &Group, color_placed: Color) {
+        let board = &shared.board;
+        let surrounding_count = board.surrounding_points(group.points[0]).count();
+        if group.points.len() == 1
+            && surrounding_count == 4
+            && board
+                .surrounding_points(group.points[0])
+                .all(|p| board.get_point(p) == color_placed)
+            && board
+                .surrounding_diagonal_points(group.points[0])
+                .all(|p| board.get_point(p) != color_placed)
+        {
+            shared.points[color_placed.0 as usize - 1] += self.0;
+        }
+    }
+}
+
+impl Rule for CapturesGivePoints {
+    fn after_capture(&self, shared: &mut SharedState, group: &Group, _color_placed: Color) {
+        let team = shared.get_active_seat().team;
+        shared.points[team.0 as usize - 1] += group.points.len() as i32 * 2;
+    }
+}
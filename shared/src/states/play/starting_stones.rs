@@ -0,0 +1,55 @@
+use rand::prelude::*;
+use rand_pcg::Lcg64Xsh32;
+
+use crate::game::{Board, Color, GameModifier, Point};
+
+/// Separate stream from traitor go so enabling one doesn't change the other.
+const STREAM: u64 = 0xcafe_f00d_d15e_a5e5;
+
+/// Places the random starting stones and neutral stones on the board.
+/// The positions only depend on the seed, so replays always get the same board.
+pub fn place(board: &mut Board, team_count: usize, seed: u64, mods: &GameModifier) {
+    if mods.random_start.is_none() && mods.neutral_stones.is_none() {
+        return;
+    }
+
+    let mut rng = Lcg64Xsh32::new(seed, STREAM);
+
+    if let Some(rule) = &mods.random_start {
+        // Alternate between teams so nobody gets a better pick of the remaining points.
+        for _ in 0..rule.stones_per_team {
+            for team in 1..=team_count as u8 {
+                place_stone(board, &mut rng, Color(team));
+            }
+        }
+    }
+
+    if let Some(rule) = &mods.neutral_stones {
+        for _ in 0..rule.count {
+            place_stone(board, &mut rng, Color::neutral());
+        }
+    }
+}
+
+/// Places a stone on a random point with no stones next to it.
+/// This way the seeded stones never start in atari or connected to each other.
+fn place_stone(board: &mut Board, rng: &mut Lcg64Xsh32, color: Color) {
+    let candidates = board
+        .points
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, _)| board.idx_to_coord(idx))
+        .filter(|&p| is_open(board, p))
+        .collect::<Vec<_>>();
+
+    if let Some(&point) = candidates.choose(rng) {
+        board.set_point(point, color);
+    }
+}
+
+fn is_open(board: &Board, point: Point) -> bool {
+    board.get_point(point).is_empty()
+        && board
+            .surrounding_points(point)
+            .all(|p| board.get_point(p).is_empty())
+}
@@ -1,6 +1,8 @@
-use crate::game::find_groups;
 use crate::game::Color;
-use crate::game::{Board, GroupVec, Point, TetrisGo};
+use crate::game::{
+    Board, GroupTracker, GroupVec, MakeActionError, MakeActionResult, Point, SharedState, TetrisGo,
+};
+use crate::states::rules::{Revealed, Rule};
 
 pub enum TetrisResult {
     Nothing,
@@ -10,27 +12,23 @@ pub enum TetrisResult {
 pub fn check(
     points_played: &mut GroupVec<Point>,
     board: &mut Board,
+    groups: &mut GroupTracker,
     _rule: &TetrisGo,
 ) -> TetrisResult {
-    let groups = find_groups(board);
-
-    for point_played in points_played.clone() {
-        let color = board.get_point(point_played);
-
-        for group in &groups {
-            if group.team != color || group.points.len() != 4 {
-                continue;
-            }
-
-            let contains = group.points.contains(&point_played);
-
-            if !contains {
-                continue;
-            }
-
-            points_played.retain(|x| *x != point_played);
-            *board.point_mut(point_played) = Color::empty();
-        }
+    // Look at the groups before removing any stones
+    let tetrominos = points_played
+        .iter()
+        .filter(|&&point| {
+            groups
+                .group_at(board, point, None)
+                .is_some_and(|group| group.points.len() == 4)
+        })
+        .copied()
+        .collect::<GroupVec<_>>();
+
+    for point_played in tetrominos {
+        points_played.retain(|x| *x != point_played);
+        board.set_point(point_played, Color::empty());
     }
 
     if points_played.is_empty() {
@@ -39,3 +37,17 @@ pub fn check(
 
     TetrisResult::Nothing
 }
+
+impl Rule for TetrisGo {
+    fn before_place(
+        &self,
+        shared: &mut SharedState,
+        points_played: &mut GroupVec<Point>,
+    ) -> MakeActionResult<Revealed> {
+        // This is valid because points_played is empty if the move is illegal.
+        match check(points_played, &mut shared.board, &mut shared.groups, self) {
+            TetrisResult::Nothing => Ok(false),
+            TetrisResult::Illegal => Err(MakeActionError::Illegal),
+        }
+    }
+}
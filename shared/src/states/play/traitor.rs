@@ -1,21 +1,30 @@
 use rand::prelude::*;
 use rand_pcg::Lcg64Xsh32;
+use serde::{Deserialize, Serialize};
 
 use crate::game::Color;
 use crate::game::GroupVec;
+use crate::game::SharedState;
 use crate::game::TraitorGo;
+use crate::states::rules::Rule;
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 struct TeamState {
     traitor_count: u32,
     stone_count: u32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TraitorState {
     /// Remaining traitors for each team
     team_states: GroupVec<TeamState>,
     rng_state: Lcg64Xsh32,
+    /// Stone colors drawn so far, so the history can store a move as the draw made in it
+    #[serde(default)]
+    draws: u64,
+    /// The team the last stone color was drawn for
+    #[serde(default = "Color::empty")]
+    last_team: Color,
 }
 
 impl TraitorState {
@@ -31,10 +40,21 @@ impl TraitorState {
             .as_slice()
             .into(),
             rng_state: Lcg64Xsh32::seed_from_u64(seed),
+            draws: 0,
+            last_team: Color::empty(),
         }
     }
 
+    /// How many stone colors were drawn since `previous`, and the team of the last one.
+    /// One draw for the team is all it takes to get from `previous` to this state.
+    pub(crate) fn draws_since(&self, previous: &TraitorState) -> (u64, Color) {
+        (self.draws.wrapping_sub(previous.draws), self.last_team)
+    }
+
     pub fn next_color(&mut self, team_color: Color) -> Color {
+        self.draws += 1;
+        self.last_team = team_color;
+
         let team = &mut self.team_states[team_color.as_usize() - 1];
         let stone_count = team.stone_count;
         team.stone_count = team.stone_count.saturating_sub(1);
@@ -53,3 +73,17 @@ impl TraitorState {
         }
     }
 }
+
+impl Rule for TraitorGo {
+    fn picks_stone_color(&self) -> bool {
+        true
+    }
+
+    /// The color is only drawn for legal moves, so trying illegal ones doesn't tell anything.
+    fn stone_color(&self, shared: &mut SharedState, team: Color) -> Color {
+        match &mut shared.traitor {
+            Some(state) => state.next_color(team),
+            None => team,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use crate::game::{Color, SharedState, ZenGo};
+use crate::states::rules::Rule;
+
+impl Rule for ZenGo {
+    /// Everyone plays the color of the move number.
+    fn after_action(&self, shared: &mut SharedState) {
+        let move_number = shared.board_history.len() - 1;
+        for seat in &mut shared.seats {
+            seat.team = Color((move_number % self.color_count as usize) as u8 + 1);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Hooks for game variants.
+//!
+//! Every modifier that changes how moves play out implements [`Rule`]. The engine builds the
+//! list of active rules from [`GameModifier`] and calls the hooks in a fixed order, so
+//! combinations of variants always resolve the same way.
+//!
+//! Some modifiers aren't rules. Alliances change what a group is, so they are part of
+//! finding groups rather than a step of a move. Hidden move go is a game state of its own,
+//! [`FreePlacement`](crate::states::FreePlacement), played before the moves start. The
+//! stones it and phantom go hide are both kept in the visibility board, so the board view
+//! hides them the same way for either before the [`Rule::on_view`] hooks run. Scoring has no
+//! hook: neutral stones can also come from a start position, so scoring always keeps them.
+
+use crate::game::{
+    Color, GameModifier, GameView, Group, GroupVec, MakeActionResult, Point, SharedState,
+};
+use crate::states::play::points::Ponnuki;
+
+/// Whether any hidden stones were revealed.
+pub type Revealed = bool;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TurnEnd {
+    /// The same player moves again.
+    pub extra_turn: bool,
+    /// The game ends right away, without marking dead stones. Teams that were knocked out
+    /// have lost.
+    pub game_over: bool,
+    /// The game moves on to scoring.
+    pub scoring: bool,
+}
+
+pub trait Rule {
+    /// Called after the stones of a move are on the board, before anything is captured.
+    /// An error rejects the move.
+    fn before_place(
+        &self,
+        _shared: &mut SharedState,
+        _points_played: &mut GroupVec<Point>,
+    ) -> MakeActionResult<Revealed> {
+        Ok(false)
+    }
+
+    /// Called for every group right after it has been removed from the board.
+    fn after_capture(&self, _shared: &mut SharedState, _group: &Group, _color_placed: Color) {}
+
+    /// Whether the rule picks the color of the stones, see [`Rule::stone_color`].
+    fn picks_stone_color(&self) -> bool {
+        false
+    }
+
+    /// Picks the color of the stones of a move that is legal with the team's own color.
+    /// Called with the state from before the move, which is then played again with the
+    /// picked color. Only the first rule that picks colors is asked.
+    fn stone_color(&self, _shared: &mut SharedState, team: Color) -> Color {
+        team
+    }
+
+    /// Called once a move is known to be legal, before the turn passes to the next player.
+    fn on_turn_end(&self, _shared: &mut SharedState, _points_played: &GroupVec<Point>) -> TurnEnd {
+        TurnEnd::default()
+    }
+
+    /// Called after every action that was accepted.
+    fn after_action(&self, _shared: &mut SharedState) {}
+
+    /// Adjusts what the given player can see.
+    fn on_view(&self, _shared: &SharedState, _player_id: u64, _view: &mut GameView) {}
+}
+
+/// Active rules in the order their hooks are called.
+pub fn rules(mods: &GameModifier) -> Vec<Box<dyn Rule>> {
+    let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+
+    if let Some(rule) = &mods.traitor {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.tetris {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.phantom {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(points) = mods.ponnuki_is_points {
+        rules.push(Box::new(Ponnuki(points)));
+    }
+    if let Some(rule) = &mods.captures_give_points {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.n_plus_one {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.king {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.last_survivor {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.move_limit {
+        rules.push(Box::new(rule.clone()));
+    }
+    if let Some(rule) = &mods.zen_go {
+        rules.push(Box::new(rule.clone()));
+    }
+
+    rules
+}
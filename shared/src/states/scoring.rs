@@ -1,6 +1,6 @@
 use crate::game::{
-    find_groups, ActionChange, ActionKind, Board, Color, GameState, Group, GroupVec,
-    MakeActionResult, Point, Seat, SharedState,
+    ActionChange, ActionKind, Board, Color, GameState, Group, GroupTracker, GroupVec,
+    MakeActionError, MakeActionResult, Point, SharedState,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::{HashSet, VecDeque};
@@ -13,23 +13,41 @@ pub struct ScoringState {
     pub scores: GroupVec<i32>,
     // TODO: use smallvec?
     pub players_accepted: Vec<bool>,
+    /// The players agreed to end the game as a draw
+    #[serde(default)]
+    pub draw: bool,
+    /// Teams that lost before the game was scored, like by losing their king
+    #[serde(default)]
+    pub lost: GroupVec<Color>,
 }
 
 impl ScoringState {
-    pub fn new(board: &Board, seats: &[Seat], scores: &[i32]) -> Self {
-        let groups = find_groups(board);
-        let points = score_board(board, &groups);
-        let mut scores: GroupVec<i32> = scores.into();
-        for color in &points.points {
-            if !color.is_empty() {
-                scores[color.0 as usize - 1] += 2;
-            }
-        }
-        ScoringState {
+    pub fn new(shared: &SharedState) -> Self {
+        let groups = GroupTracker::default().groups(&shared.board, shared.mods.alliances.as_ref());
+        let mut scoring = ScoringState {
             groups,
-            points,
-            scores,
-            players_accepted: seats.iter().map(|s| s.resigned).collect(),
+            points: Board::empty(
+                shared.board.width,
+                shared.board.height,
+                shared.board.toroidal,
+            ),
+            scores: GroupVec::new(),
+            players_accepted: shared.seats.iter().map(|s| s.resigned).collect(),
+            draw: false,
+            lost: GroupVec::new(),
+        };
+        scoring.count(shared);
+        scoring
+    }
+
+    /// Scores the board with the groups as they are marked.
+    fn count(&mut self, shared: &SharedState) {
+        self.points = score_board(&shared.board, &self.groups);
+        self.scores = shared.points.clone();
+        for color in &self.points.points {
+            if !color.is_empty() && !color.is_neutral() {
+                self.scores[color.0 as usize - 1] += 2;
+            }
         }
     }
 
@@ -41,19 +59,14 @@ impl ScoringState {
         let group = self.groups.iter_mut().find(|g| g.points.contains(&point));
 
         let group = match group {
-            Some(g) => g,
-            None => return Ok(ActionChange::None),
+            // Neutral stones belong to nobody, so they can't be marked dead
+            Some(g) if !g.team.is_neutral() => g,
+            _ => return Ok(ActionChange::None),
         };
 
         group.alive = !group.alive;
 
-        self.points = score_board(&shared.board, &self.groups);
-        self.scores = shared.points.clone();
-        for color in &self.points.points {
-            if !color.is_empty() {
-                self.scores[color.0 as usize - 1] += 2;
-            }
-        }
+        self.count(shared);
 
         for (idx, accept) in self.players_accepted.iter_mut().enumerate() {
             *accept = shared.seats[idx].resigned;
@@ -88,14 +101,20 @@ impl ScoringState {
         // A single player can hold multiple seats so we have to mark every seat they hold
         let seats = shared
             .seats
-            .iter_mut()
+            .iter()
             .enumerate()
-            .filter(|x| x.1.player == Some(player_id));
+            .filter(|(_, seat)| seat.player == Some(player_id))
+            .map(|(idx, _)| idx)
+            .collect::<GroupVec<_>>();
+
+        shared.resign_seats(&seats);
 
-        for (seat_idx, seat) in seats {
-            seat.resigned = true;
+        for &seat_idx in &seats {
             self.players_accepted[seat_idx] = true;
         }
+        for (accepted, seat) in self.players_accepted.iter_mut().zip(&shared.seats) {
+            *accepted |= seat.resigned;
+        }
 
         if self.players_accepted.iter().all(|x| *x) {
             Ok(ActionChange::SwapState(GameState::Done(self.clone())))
@@ -111,31 +130,34 @@ impl ScoringState {
         action: ActionKind,
     ) -> MakeActionResult {
         match action {
-            ActionKind::Place(x, y) => self.make_action_place(shared, (x, y)),
+            ActionKind::Place(x, y) | ActionKind::PlaceShape(x, y, _) => {
+                self.make_action_place(shared, (x, y))
+            }
             ActionKind::Pass => self.make_action_pass(shared, player_id),
             ActionKind::Cancel => Ok(ActionChange::PopState),
             ActionKind::Resign => self.make_action_resign(shared, player_id),
+            ActionKind::ProposeDraw => Err(MakeActionError::Illegal),
         }
     }
 }
 
 /// Scores a board by filling in fully surrounded empty spaces based on chinese rules
-fn score_board(board: &Board, groups: &[Group]) -> Board {
+fn score_board(stones: &Board, groups: &[Group]) -> Board {
     let &Board {
         width,
         height,
         toroidal,
         ..
-    } = board;
+    } = stones;
     let mut board = Board::empty(width, height, toroidal);
 
-    // Fill living groups to the board
+    // Fill living groups to the board. Allied groups have stones of more than one team.
     for group in groups {
         if !group.alive {
             continue;
         }
-        for point in &group.points {
-            *board.point_mut(*point) = group.team;
+        for &point in &group.points {
+            board.set_point(point, stones.get_point(point));
         }
     }
 
@@ -182,6 +204,8 @@ fn score_board(board: &Board, groups: &[Group]) -> Board {
                         stack.push_back(point);
                         legal_points.retain(|x| *x != point);
                     }
+                    // Neutral stones are walls that don't claim territory
+                    c if c.is_neutral() => {}
                     c => {
                         collisions = match collisions {
                             Zero => One(c),
@@ -197,7 +221,7 @@ fn score_board(board: &Board, groups: &[Group]) -> Board {
         // The floodfill touched only a single color -> this must be their territory
         if let One(color) = collisions {
             for point in marked.drain(..) {
-                *board.point_mut(point) = color;
+                board.set_point(point, color);
             }
         }
 